@@ -8,6 +8,8 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART1])]
 mod app {
 
+    use appcore::sleep::SleepMeter;
+    use nucleis::power::{debug_in_sleep, sleep, SLEEP_REPORT_PERIOD};
     use stm32f4xx_hal::{
         gpio::gpioa::PA5,
         gpio::{Output, PushPull},
@@ -42,6 +44,7 @@ mod app {
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
         let device = cx.device;
+        debug_in_sleep(&device.DBGMCU);
 
         // Set up the system clocks
         //  constrain() promotes a PAC-level struct to a HAL, more in Omar Hiari article
@@ -56,12 +59,12 @@ mod app {
         blink::spawn().ok();
         (Shared {}, Local { led }, init::Monotonics(mono))
     }
-    #[idle]
-    fn idle(_: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         defmt::info!("idle");
 
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
@@ -7,78 +7,79 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
 
-    use stm32f4xx_hal::{
-        gpio::gpioa::{PA10, PA5, PA9},
-        gpio::{Alternate, Output, PushPull},
-        pac::USART1,
-        prelude::*,
-        serial::{config::Config as UartConfig, Event, Serial},
-        timer::{monotonic::MonoTimer, Timer},
+    use appcore::sleep::SleepMeter;
+    use nucleis::{
+        board::{Led, Mono, Nucleo},
+        dma::{DmaRx, RX_RING},
+        power::{sleep, SLEEP_REPORT_PERIOD},
     };
-
-    // This is an alias to define USART, that needs to pins in alternate mode 7
-    type SandwichUart =
-        Serial<USART1, (PA9<Alternate<PushPull, 7>>, PA10<Alternate<PushPull, 7>>), u8>;
+    use stm32f4xx_hal::{hal::digital::v2::OutputPin, prelude::*};
 
     // you need a monotonic clock. DWTSystick is the poor parent of clocks.
-    #[monotonic(binds = TIM2, default = true)]
-    type Monotonic = MonoTimer<stm32f4xx_hal::pac::TIM2, 1_000_000>;
+    #[monotonic(binds = TIM5, default = true)]
+    type Monotonic = Mono;
 
     #[shared]
-    struct Shared {}
+    struct Shared {
+        // Both receive interrupts run at the same priority.
+        #[lock_free]
+        rx: DmaRx,
+        #[lock_free]
+        led: Led,
+    }
 
     #[local]
-    struct Local {
-        led: PA5<Output<PushPull>>,
-        usart: SandwichUart,
-    }
+    struct Local {}
 
-    #[init]
+    // The DMA writes in the ring for the whole program, it is static when initiated there
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-
-        // Set up the system clocks
-        let rcc = device.RCC.constrain();
-        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
-
-        let mono = Timer::new(device.TIM2, &clocks).monotonic();
-        let gpioa = device.GPIOA.split();
-        let usart_rx = gpioa.pa10.into_alternate();
-        let usart_tx = gpioa.pa9.into_alternate();
-        let led = gpioa.pa5.into_push_pull_output();
-        let mut usart = Serial::new(
-            device.USART1,
-            (usart_tx, usart_rx),
-            UartConfig::default().baudrate(9600.bps()),
-            &clocks,
+        // Clocks, led, UART and its DMA, see `nucleis::board`
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
+        (
+            Shared {
+                rx: board.rx,
+                led: board.led,
+            },
+            Local {},
+            init::Monotonics(mono),
         )
-        .unwrap();
-        usart.listen(Event::Rxne);
-        (Shared {}, Local { usart, led }, init::Monotonics(mono))
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
-    #[task(binds=USART1, local=[usart, led])]
+    // The line went idle after the byte
+    #[task(binds=USART1, shared=[rx, led])]
     fn interupting(cx: interupting::Context) {
-        if let Ok(d) = cx.local.usart.read() {
+        switch(cx.shared.rx, cx.shared.led);
+    }
+
+    // The ring is half or completely full, while the line is still busy
+    #[task(binds=DMA2_STREAM2, shared=[rx, led])]
+    fn ring_rx(cx: ring_rx::Context) {
+        switch(cx.shared.rx, cx.shared.led);
+    }
+
+    fn switch(rx: &mut DmaRx, led: &mut Led) {
+        rx.on_interrupt();
+        while let Ok(d) = rx.read() {
             match d {
                 1 => {
                     defmt::debug!("Received {:?} 🟢 , turning on the light!", d);
-                    cx.local.led.set_high();
+                    led.set_high().ok();
                 }
                 0 => {
                     defmt::debug!("Received {:?} 🔴 , turning off the light!", d);
-                    cx.local.led.set_low();
+                    led.set_low().ok();
                 }
                 _ => {
-                    cx.local.led.set_low();
+                    led.set_low().ok();
                     defmt::debug!("Received noise, d = {:?}.", d)
                 }
             }
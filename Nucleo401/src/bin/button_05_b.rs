@@ -6,94 +6,97 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
-    use stm32f4xx_hal::{
-        gpio::{
-            gpioa::{PA10, PA9},
-            gpioc::PC13,
-        },
-        gpio::{Alternate, Edge, Input, PullUp, PushPull},
-        pac::USART1,
-        prelude::*,
-        serial::{config::Config as UartConfig, Event, Serial},
-        timer::{monotonic::MonoTimer, Timer},
+    use appcore::{
+        board::{read_gestures, Board},
+        gesture::{Button, Gesture, Timings},
+        sleep::SleepMeter,
     };
-
-    // This is an alias to define USART, that needs to pins in alternate mode 7
-    type SandwichUart =
-        Serial<USART1, (PA9<Alternate<PushPull, 7>>, PA10<Alternate<PushPull, 7>>), u8>;
+    use nucleis::{
+        board::{Mono, Nucleo},
+        dma::RX_RING,
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use stm32f4xx_hal::prelude::*;
 
     // you need a monotonic clock. DWTSystick is the poor parent of clocks.
-    #[monotonic(binds = TIM2, default = true)]
-    type Monotonic = MonoTimer<stm32f4xx_hal::pac::TIM2, 1_000_000>;
+    #[monotonic(binds = TIM5, default = true)]
+    type Monotonic = Mono;
 
     #[shared]
     struct Shared {
-        button: PC13<Input<PullUp>>,
+        // B1 and the UART
+        board: Nucleo,
+        gestures: [Button; 1],
     }
 
     #[local]
-    struct Local {
-        usart: SandwichUart,
-    }
+    struct Local {}
 
-    #[init]
+    // The DMA writes in the ring for the whole program, even if nothing comes
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let mut device = cx.device;
-
-        // Set up the system clocks
-        let rcc = device.RCC.constrain();
-        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
-        let mut syscfg = device.SYSCFG.constrain();
-        let mono = Timer::new(device.TIM2, &clocks).monotonic();
-        let gpioa = device.GPIOA.split();
-        let usart_rx = gpioa.pa10.into_alternate();
-        let usart_tx = gpioa.pa9.into_alternate();
-        let gpioc = device.GPIOC.split();
-        let mut button = gpioc.pc13.into_pull_up_input();
-
-        button.make_interrupt_source(&mut syscfg);
-        button.enable_interrupt(&mut device.EXTI);
-        button.trigger_on_edge(&mut device.EXTI, Edge::Falling);
-
-        let mut usart = Serial::new(
-            device.USART1,
-            (usart_tx, usart_rx),
-            UartConfig::default().baudrate(9600.bps()),
-            &clocks,
+        // Clocks, button and its interrupt, UART, see `nucleis::board`
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
+        (
+            Shared {
+                board,
+                gestures: [Button::new(Timings::DEFAULT)],
+            },
+            Local {},
+            init::Monotonics(mono),
         )
-        .unwrap();
-        usart.listen(Event::Rxne);
-        (Shared { button }, Local { usart }, init::Monotonics(mono))
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
-    #[task(binds = EXTI15_10, priority=2, shared = [button])]
+    /// A press, or a bounce: the level is read once it settled, see `appcore::gesture`.
+    #[task(binds = EXTI15_10, priority=2, shared = [board, gestures])]
     fn button_click(mut ctx: button_click::Context) {
         defmt::debug!("Button pushed");
-        ctx.shared.button.lock(|b| b.clear_interrupt_pending_bit());
-        send::spawn_after(40.millis()).ok();
+        ctx.shared
+            .board
+            .lock(|board| board.buttons[0].clear_interrupt_pending_bit());
+        ctx.shared.gestures.lock(|[gestures]| {
+            if let Some(at) = gestures.on_edge(monotonics::now()) {
+                poll_button::spawn_at(at).ok();
+            }
+        });
     }
-    #[task(priority=1, local=[usart, is_on : bool = false], shared=[button])]
-    fn send(mut cx: send::Context) {
-        let mut b = 0;
-        if cx.shared.button.lock(|b| b.is_low()) {
-            if *cx.local.is_on {
-                b = 0;
-                *cx.local.is_on = false;
-            } else {
-                b = 1;
-                *cx.local.is_on = true;
+
+    /// Reads the button until it is released and settled. A press, or a double click,
+    /// switches the led.
+    #[task(priority=1, shared = [board, gestures])]
+    fn poll_button(cx: poll_button::Context) {
+        (cx.shared.board, cx.shared.gestures).lock(|board, gestures| {
+            let [gesture] = read_gestures(board, gestures, monotonics::now());
+            if let Some(at) = gestures[0].deadline() {
+                poll_button::spawn_at(at).ok();
             }
-        }
+            match gesture {
+                Some(Gesture::Press | Gesture::DoubleClick) => {
+                    send::spawn().ok();
+                }
+                Some(gesture) => defmt::debug!("{:?}", gesture),
+                None => {}
+            }
+        });
+    }
+
+    #[task(priority=1, shared=[board], local=[is_on: bool = false])]
+    fn send(mut cx: send::Context) {
+        let b = if *cx.local.is_on { 0 } else { 1 };
+        *cx.local.is_on = !*cx.local.is_on;
         defmt::info!("Sending byte {}", b);
-        let _ = cx.local.usart.write(b);
-        let _ = cx.local.usart.flush();
+        cx.shared.board.lock(|board| {
+            let (tx, _) = board.uart();
+            let _ = tx.write(b);
+            let _ = tx.flush();
+        });
     }
 }
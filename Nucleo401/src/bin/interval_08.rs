@@ -1,4 +1,9 @@
 //! The nRF52 is blinking the light of the nucleo, with intervals. The light can be dimmed.
+//! When the nRF52 stops sending its heartbeat, the light goes to a failsafe blink.
+//! It can also play a pattern instead of blinking: breathing, heartbeat, strobe, Morse
+//! code, or steps uploaded by the nRF52.
+//! An RGB led on TIM3 adds the channels 1 to 3, see `nucleis::board::Rgb`. Each one blinks
+//! and plays its patterns like LD2, with the commands of its `Command::Channel`.
 
 #![no_main]
 #![no_std]
@@ -7,152 +12,211 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use postcard::from_bytes_cobs;
-
-    use serde::{Deserialize, Serialize};
-    use stm32f4xx_hal::{
-        gpio::gpioa::{PA10, PA9},
-        gpio::{Alternate, PushPull},
-        pac::{TIM2, USART1},
-        prelude::*,
-        pwm::PwmChannel,
-        serial::{config::Config as UartConfig, Event, Serial},
-        timer::{monotonic::MonoTimer, Timer, C1},
+    use appcore::{
+        blinker::{Blinker, MAX_MISSED},
+        channels::{for_channel, CHANNELS},
+        pattern::Sequencer,
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport},
     };
+    use nucleis::{
+        board::{Led, Link, Mono, Nucleo, Rgb},
+        dma::RX_RING,
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        link::{Transition, HEARTBEAT_PERIOD},
+        Command, Duration, Instant,
+    };
+    use stm32f4xx_hal::nb;
 
-    type SandwichUart =
-        Serial<USART1, (PA9<Alternate<PushPull, 7>>, PA10<Alternate<PushPull, 7>>), u8>;
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
 
     #[monotonic(binds = TIM5, default = true)]
-    type Monotonic = MonoTimer<stm32f4xx_hal::pac::TIM5, 1_000_000>;
-
-    #[derive(Serialize, Deserialize, Format, Clone, Copy)]
+    type Monotonic = Mono;
 
-    pub enum Command {
-        On,
-        Off,
-        Pwm(u16),
-        Interval(u8),
-    }
     #[shared]
     struct Shared {
+        // brightness and interval of every channel, and link state, see `appcore::blinker`
+        #[lock_free]
+        blinker: Blinker<CHANNELS>,
+        // Both receive interrupts run at the same priority.
+        #[lock_free]
+        link: Link,
+        // the pattern playing instead of the blink, of every channel, see `appcore::pattern`
         #[lock_free]
-        brightness: u16,
+        sequencers: [Sequencer; CHANNELS],
+        // The next `beat` of every channel, to play a new pattern right away
         #[lock_free]
-        time: u8,
+        next_beat: [Option<beat::SpawnHandle>; CHANNELS],
+        // pwm has now the led, they are inseparable!
+        // `blink` and `beat` run at the same priority.
+        #[lock_free]
+        led: Led,
+        // channels 1 to 3
+        #[lock_free]
+        rgb: Rgb,
     }
 
     #[local]
-    struct Local {
-        usart: SandwichUart,
-        buf: Vec<u8, 16>,
-        // pwm has now the led, they are inseparable!
-        pwm_channel: PwmChannel<TIM2, C1>,
-    }
+    struct Local {}
 
-    #[init]
+    // The DMA writes in the ring for the whole program, it is static when initiated there
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        // Set up the system clocks
-        let rcc = device.RCC.constrain();
-        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
-
-        let mono = Timer::new(device.TIM5, &clocks).monotonic();
-        let gpioa = device.GPIOA.split();
-        let usart_rx = gpioa.pa10.into_alternate();
-        let usart_tx = gpioa.pa9.into_alternate();
-        let led = gpioa.pa5.into_alternate();
-        let mut pwm_channel = Timer::new(device.TIM2, &clocks).pwm(led, 20.khz());
-        pwm_channel.enable();
-
-        let mut time = 1;
-        let brightness = pwm_channel.get_max_duty();
-        let buf = Vec::new();
-        let mut usart = Serial::new(
-            device.USART1,
-            (usart_tx, usart_rx),
-            UartConfig::default().baudrate(9600.bps()),
-            &clocks,
-        )
-        .unwrap();
-        blink::spawn();
-        usart.listen(Event::Rxne);
+        // Clocks, pwms, UART and its DMA, see `nucleis::board`
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
+        let max_duty = board.led.0.get_max_duty();
+        for channel in 0..CHANNELS {
+            blink::spawn(channel).ok();
+        }
+        supervise::spawn().ok();
         (
-            Shared { brightness, time },
-            Local {
-                usart,
-                pwm_channel,
-                buf,
+            Shared {
+                blinker: Blinker::channels(max_duty),
+                link: SerialTransport::new(board.tx, board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+                sequencers: [(); CHANNELS].map(|_| Sequencer::new(max_duty)),
+                next_beat: Default::default(),
+                led: board.led,
+                rgb: board.rgb,
             },
+            Local {},
             init::Monotonics(mono),
         )
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
-    // A hardware task must do only the dispatching
-    #[task(binds=USART1, priority = 2, local=[usart])]
+    // The line went idle after some bytes: a frame, or a part of it, is in the ring
+    #[task(binds=USART1, priority = 2, shared=[link])]
     fn command_rx(cx: command_rx::Context) {
-        while let Ok(d) = cx.local.usart.read() {
-            parse::spawn(d).unwrap();
+        dispatch(cx.shared.link);
+    }
+
+    // The ring is half or completely full, while the line is still busy
+    #[task(binds=DMA2_STREAM2, priority = 2, shared=[link])]
+    fn ring_rx(cx: ring_rx::Context) {
+        dispatch(cx.shared.link);
+    }
+
+    // A hardware task must do only the dispatching: whole frames, to the parser
+    fn dispatch(link: &mut Link) {
+        link.rx().on_interrupt();
+        loop {
+            let at = monotonics::now();
+            match link.receive::<Command>(at) {
+                Ok(command) => {
+                    if parse::spawn(command, at).is_err() {
+                        defmt::warn!("Too many commands, {:?} is dropped.", command);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped())
+                }
+            }
         }
     }
 
     // The lower priority software task handles the message
-    #[task(capacity = 16, priority = 1, shared=[brightness, time], local=[buf])]
-    fn parse(cx: parse::Context, d: u8) {
-        let _ = cx.local.buf.push(d);
-
-        // 0 is the terminating byte of the Postcard serializer
-        if d == 0 {
-            if let Ok(command) = from_bytes_cobs(cx.local.buf) {
-                defmt::debug!("Received complete command: {:?}.", command);
-                match command {
-                    Command::On => {
-                        *cx.shared.brightness = 255;
-                    }
-                    Command::Off => {
-                        *cx.shared.brightness = 0;
+    #[task(capacity = 4, priority = 1, shared=[blinker, sequencers, next_beat])]
+    fn parse(cx: parse::Context, command: Command, at: Instant) {
+        defmt::debug!("Received complete command: {:?}.", command);
+        if let Some(transition) = cx.shared.blinker.on_command(command, at) {
+            log_link(transition);
+        }
+        for (channel, sequencer) in cx.shared.sequencers.iter_mut().enumerate() {
+            let command = for_channel(command, channel);
+            if command.is_some_and(|command| sequencer.apply(command)) {
+                restart_beat(&mut cx.shared.next_beat[channel], channel);
+            }
+        }
+    }
+
+    /// Checks every heartbeat period that the nRF52 is still there.
+    /// The failsafe blink stops the patterns.
+    #[task(shared=[blinker, sequencers, next_beat])]
+    fn supervise(cx: supervise::Context) {
+        if let Some(transition) = cx.shared.blinker.link.poll(monotonics::now()) {
+            log_link(transition);
+            if transition == Transition::Down {
+                for (channel, sequencer) in cx.shared.sequencers.iter_mut().enumerate() {
+                    if sequencer.stop() {
+                        restart_beat(&mut cx.shared.next_beat[channel], channel);
                     }
-                    Command::Pwm(level) => match level {
-                        0..=10 => *cx.shared.brightness = 5,
-                        11..=30 => *cx.shared.brightness = 20,
-                        31..=80 => *cx.shared.brightness = 70,
-                        81..=130 => *cx.shared.brightness = 110,
-                        131..=170 => *cx.shared.brightness = 150,
-                        171..=200 => *cx.shared.brightness = 180,
-                        201..=230 => *cx.shared.brightness = 240,
-                        231..=u16::MAX => *cx.shared.brightness = 255,
-                    },
-                    Command::Interval(sec) => *cx.shared.time = sec,
                 }
             }
-            //Clear också om from_bytes failar
-            cx.local.buf.clear();
-        };
+        }
+        supervise::spawn_after(HEARTBEAT_PERIOD).ok();
+    }
+
+    /// Shows the first step of the new pattern of `channel` now, instead of at the end
+    /// of the step of the old one. Scheduled rather than spawned, for a handle: another
+    /// command before the beat runs must be able to cancel it too, or two beats would
+    /// step the same pattern.
+    fn restart_beat(next_beat: &mut Option<beat::SpawnHandle>, channel: usize) {
+        if let Some(handle) = next_beat.take() {
+            handle.cancel().ok();
+        }
+        *next_beat = beat::spawn_after(Duration::from_ticks(0), channel).ok();
+    }
+
+    /// LD2 is channel 0, the RGB led the next ones.
+    fn set_duty(led: &mut Led, rgb: &mut Rgb, channel: usize, duty: u16) {
+        match channel {
+            0 => led.0.set_duty(duty),
+            _ => rgb.set_duty(channel - 1, duty),
+        }
+    }
+
+    fn log_link(transition: Transition) {
+        match transition {
+            Transition::Up => defmt::info!("Link up 🔗"),
+            Transition::Down => defmt::warn!(
+                "Link down, {} heartbeats missed, going to failsafe.",
+                MAX_MISSED
+            ),
+        }
     }
 
     /// This task is setting the light on or off with an interval.
     /// It reads the brightness (a shared resource) and spawn itself after a delay
     /// that you decide with the nRF52 buttons!
-    #[task(shared=[brightness, time], local=[pwm_channel, powered: bool = false])]
-    fn blink(cx: blink::Context) {
-        let level = cx.shared.brightness;
-        if *cx.local.powered {
-            cx.local.pwm_channel.set_duty((*level * 8) as u16);
-            *cx.local.powered = false;
-        } else {
-            cx.local.pwm_channel.set_duty(0);
-            *cx.local.powered = true;
+    /// While the link is down, it blinks the failsafe instead.
+    /// While a pattern plays, the blink keeps its pace in the dark.
+    /// Every channel has its own, and shows its fade while lit.
+    #[task(capacity = 4, shared=[blinker, sequencers, led, rgb])]
+    fn blink(cx: blink::Context, channel: usize) {
+        let blink = cx.shared.blinker.blink_channel(channel, monotonics::now());
+        if !cx.shared.sequencers[channel].is_playing() {
+            set_duty(cx.shared.led, cx.shared.rgb, channel, blink.duty);
         }
-        blink::spawn_after((*cx.shared.time as u32).secs()).ok();
+        blink::spawn_after(blink.next_in, channel).ok();
+    }
+
+    /// Shows a step of the pattern of `channel` and spawns itself after it, until the
+    /// pattern is over. The blink shows again from then.
+    #[task(capacity = 4, shared=[sequencers, next_beat, led, rgb])]
+    fn beat(cx: beat::Context, channel: usize) {
+        cx.shared.next_beat[channel] = match cx.shared.sequencers[channel].beat() {
+            Some(beat) => {
+                set_duty(cx.shared.led, cx.shared.rgb, channel, beat.duty);
+                beat::spawn_after(beat.next_in, channel).ok()
+            }
+            None => {
+                defmt::debug!("Pattern over.");
+                None
+            }
+        };
     }
 }
@@ -7,6 +7,8 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
 
+    use appcore::sleep::SleepMeter;
+    use nucleis::power::{debug_in_sleep, sleep, SLEEP_REPORT_PERIOD};
     use stm32f4xx_hal::{
         gpio::gpioa::{PA10, PA5, PA9},
         gpio::{Alternate, Output, PushPull},
@@ -35,6 +37,7 @@ mod app {
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
         let device = cx.device;
+        debug_in_sleep(&device.DBGMCU);
 
         // Set up the system clocks
         let rcc = device.RCC.constrain();
@@ -56,10 +59,10 @@ mod app {
         (Shared {}, Local { usart, led }, init::Monotonics(mono))
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
@@ -6,6 +6,7 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART1])]
 mod app {
+    use nucleis::power::debug_in_sleep;
 
     #[shared]
     struct Shared {}
@@ -14,8 +15,9 @@ mod app {
     struct Local {}
 
     #[init]
-    fn init(_cx: init::Context) -> (Shared, Local, init::Monotonics) {
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
+        debug_in_sleep(&cx.device.DBGMCU);
 
         task1::spawn().ok();
 
@@ -27,8 +29,9 @@ mod app {
     fn idle(_: idle::Context) -> ! {
         defmt::info!("idle");
 
+        // No timer here to measure the sleep, see `power::sleep` in the next programs
         loop {
-            continue;
+            cortex_m::asm::wfi();
         }
     }
 
@@ -7,110 +7,111 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use postcard::{from_bytes_cobs, to_slice_cobs};
-    use serde::{Deserialize, Serialize};
-    use stm32f4xx_hal::{
-        gpio::gpioa::{PA10, PA5, PA9},
-        gpio::{Alternate, Output, PushPull},
-        pac::USART1,
-        prelude::*,
-        serial::{config::Config as UartConfig, Event, Serial},
-        timer::{monotonic::MonoTimer, Timer},
+    use appcore::{
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport, Unconnected},
     };
+    use nucleis::{
+        board::{Led, Mono, Nucleo},
+        dma::{DmaRx, RX_RING},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        Command, Duration,
+    };
+    use stm32f4xx_hal::{hal::digital::v2::OutputPin, nb, serial::Error};
 
-    type SandwichUart =
-        Serial<USART1, (PA9<Alternate<PushPull, 7>>, PA10<Alternate<PushPull, 7>>), u8>;
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
 
-    #[monotonic(binds = TIM2, default = true)]
-    type Monotonic = MonoTimer<stm32f4xx_hal::pac::TIM2, 1_000_000>;
+    /// Only receives, the Nucleo has nothing to say.
+    type Receiver = SerialTransport<Unconnected<Error>, DmaRx>;
+
+    #[monotonic(binds = TIM5, default = true)]
+    type Monotonic = Mono;
 
-    // This is the Command that will be received instead of 0 or 1
-    #[derive(Serialize, Deserialize, Format, Clone, Copy)]
-    pub enum Command {
-        On,
-        Off,
-    }
     #[shared]
-    struct Shared {}
+    struct Shared {
+        // Both receive interrupts run at the same priority.
+        #[lock_free]
+        link: Receiver,
+    }
 
     #[local]
     struct Local {
-        led: PA5<Output<PushPull>>,
-        usart: SandwichUart,
-        buf: Vec<u8, 3>,
+        led: Led,
     }
 
-    #[init]
+    // The DMA writes in the ring for the whole program, it is static when initiated there
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        // Set up the system clocks
-        let rcc = device.RCC.constrain();
-        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
-
-        let mono = Timer::new(device.TIM2, &clocks).monotonic();
-        let gpioa = device.GPIOA.split();
-        let usart_rx = gpioa.pa10.into_alternate();
-        let usart_tx = gpioa.pa9.into_alternate();
-        let led = gpioa.pa5.into_push_pull_output();
-        let buf = Vec::new();
-        let mut usart = Serial::new(
-            device.USART1,
-            (usart_tx, usart_rx),
-            UartConfig::default().baudrate(9600.bps()),
-            &clocks,
+        // Clocks, UART and its DMA, see `nucleis::board`
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
+        (
+            Shared {
+                link: SerialTransport::receiver(board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+            },
+            Local { led: board.led },
+            init::Monotonics(mono),
         )
-        .unwrap();
-        usart.listen(Event::Rxne);
-        (Shared {}, Local { usart, led, buf }, init::Monotonics(mono))
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
-    /// This tasks received the bytes and sends them to processing.
-    /// It has higher priority than the processing!
-    /// The hardware task must preempt.
-    /// https://rtic.rs/0.5/book/en/by-example/app.html#priorities
-    #[task(binds=USART1, priority = 2, local=[usart])]
+    // The line went idle after some bytes: a frame, or a part of it, is in the ring
+    #[task(binds=USART1, priority = 2, shared=[link])]
     fn command_rx(cx: command_rx::Context) {
-        if let Ok(d) = cx.local.usart.read() {
-            parse::spawn(d).ok();
-        }
+        dispatch(cx.shared.link);
     }
 
-    /// This tasks deserializes a message from a buffer.
-    /// When 0, that indicates message termination is received,
-    /// we will convert the buffer to a command and act on the light.
-    #[task(capacity = 16, priority = 1, local=[led, buf])]
-    fn parse(cx: parse::Context, d: u8) {
-        defmt::debug!("cx.local.buf: {:?}.", cx.local.buf.as_slice());
+    // The ring is half or completely full, while the line is still busy
+    #[task(binds=DMA2_STREAM2, priority = 2, shared=[link])]
+    fn ring_rx(cx: ring_rx::Context) {
+        dispatch(cx.shared.link);
+    }
 
-        let _ = cx.local.buf.push(d);
-        // terminating byte
-        if d == 0 {
-            if let Ok(command) = from_bytes_cobs(cx.local.buf) {
-                match command {
-                    Command::On => {
-                        defmt::debug!("Received {:?}!", command);
-                        cx.local.led.set_high();
-                        cx.local.buf.clear();
-                    }
-                    Command::Off => {
-                        defmt::debug!("Received {:?}!", command);
-                        cx.local.led.set_low();
-                        cx.local.buf.clear();
+    /// The hardware tasks have higher priority than the processing, they must preempt:
+    /// https://rtic.rs/0.5/book/en/by-example/app.html#priorities
+    /// They only hand the whole frames to the parser.
+    fn dispatch(link: &mut Receiver) {
+        link.rx().on_interrupt();
+        loop {
+            match link.receive::<Command>(monotonics::now()) {
+                Ok(command) => {
+                    if parse::spawn(command).is_err() {
+                        defmt::warn!("Too many commands, {:?} is dropped.", command);
                     }
                 }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped())
+                }
             }
-            //Clear också om from_bytes failar
-            cx.local.buf.clear();
-        };
+        }
+    }
+
+    /// This tasks acts on the light with the command, once a whole frame is received.
+    #[task(capacity = 4, priority = 1, local=[led])]
+    fn parse(cx: parse::Context, command: Command) {
+        match command {
+            Command::On => {
+                defmt::debug!("Received {:?}!", command);
+                cx.local.led.set_high().ok();
+            }
+            Command::Off => {
+                defmt::debug!("Received {:?}!", command);
+                cx.local.led.set_low().ok();
+            }
+            _ => defmt::debug!("Ignoring {:?}, the led is not dimmable.", command),
+        }
     }
 }
@@ -1,5 +1,4 @@
-//! The nRF52 is dimming the light of the Nucleo.
-//! ATM the dimmer function is bad, and need to be improved by making a function to dim, instead of magic numbers.
+//! The nRF52 is dimming the light of the Nucleo, right away or with a fade.
 #![no_main]
 #![no_std]
 
@@ -7,144 +6,142 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use postcard::from_bytes_cobs;
-    use serde::{Deserialize, Serialize};
-    use stm32f4xx_hal::{
-        gpio::gpioa::{PA10, PA9},
-        gpio::{Alternate, PushPull},
-        pac::{TIM2, USART1},
-        prelude::*,
-        pwm::PwmChannel,
-        serial::{config::Config as UartConfig, Event, Serial},
-        timer::{monotonic::MonoTimer, Timer, C1},
+    use appcore::{
+        brightness::Curve,
+        dimmer::Dimmer,
+        fade::FADE_STEP,
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport, Unconnected},
     };
+    use nucleis::{
+        board::{Led, Mono, Nucleo},
+        dma::{DmaRx, RX_RING},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        Command, Duration,
+    };
+    use stm32f4xx_hal::{hal::PwmPin, nb, serial::Error};
 
-    type SandwichUart =
-        Serial<USART1, (PA9<Alternate<PushPull, 7>>, PA10<Alternate<PushPull, 7>>), u8>;
+    /// The nRF52 does not listen, this Nucleo only receives.
+    type Receiver = SerialTransport<Unconnected<Error>, DmaRx>;
+
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
+    /// How the levels of the nRF52 look on the led.
+    const CURVE: Curve = Curve::DEFAULT;
 
     #[monotonic(binds = TIM5, default = true)]
-    type Monotonic = MonoTimer<stm32f4xx_hal::pac::TIM5, 1_000_000>;
+    type Monotonic = Mono;
 
-    // This is the Command that will be received instead of 0 or 1
-    // It has been augmented with a Pwm() with an inner level
-    // We use u16 because .get_max_duty() of pwm returns a u16.
-    // But on the other side we sill use a u8 as it makes more sense for our function :)
-    #[derive(Serialize, Deserialize, Format, Clone, Copy)]
-    pub enum Command {
-        On,
-        Off,
-        Pwm(u16),
-    }
     #[shared]
-    struct Shared {}
-
-    #[local]
-    struct Local {
-        usart: SandwichUart,
-        buf: Vec<u8, 16>,
+    struct Shared {
+        // Both receive interrupts run at the same priority.
+        #[lock_free]
+        link: Receiver,
+        // `parse` and `fade` run at the same priority too.
+        #[lock_free]
+        dimmer: Dimmer,
         // pwm has now the led, they are inseparable!
         // aka: you cannont use the led as a peripheral now it is
         // owned by the pwm
-        pwm_channel: PwmChannel<TIM2, C1>,
+        #[lock_free]
+        led: Led,
     }
 
-    #[init]
+    #[local]
+    struct Local {}
+
+    // The DMA writes in the ring for the whole program, it is static when initiated there
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        // Set up the system clocks
-        let rcc = device.RCC.constrain();
-        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
-
-        let mono = Timer::new(device.TIM5, &clocks).monotonic();
-        let gpioa = device.GPIOA.split();
-        let usart_rx = gpioa.pa10.into_alternate();
-        let usart_tx = gpioa.pa9.into_alternate();
-        let led = gpioa.pa5.into_alternate();
-        let mut pwm_channel = Timer::new(device.TIM2, &clocks).pwm(led, 20.khz());
-        pwm_channel.enable();
-
-        let buf = Vec::new();
-        let mut usart = Serial::new(
-            device.USART1,
-            (usart_tx, usart_rx),
-            UartConfig::default().baudrate(9600.bps()),
-            &clocks,
-        )
-        .unwrap();
-        usart.listen(Event::Rxne);
+        // Clocks, pwm, UART and its DMA, see `nucleis::board`. The DMA reads the UART,
+        // an interrupt comes when the line is idle.
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
         (
-            Shared {},
-            Local {
-                usart,
-                pwm_channel,
-                buf,
+            Shared {
+                link: SerialTransport::receiver(board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+                dimmer: Dimmer::new(CURVE, board.led.get_max_duty()),
+                led: board.led,
             },
+            Local {},
             init::Monotonics(mono),
         )
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
     /// This task is a hardware task that does only dispatching
     /// And has highest priority.
-    #[task(binds=USART1, priority = 2, local=[usart])]
+    /// The line went idle after some bytes: a frame, or a part of it, is in the ring.
+    #[task(binds=USART1, priority = 2, shared=[link])]
     fn command_rx(cx: command_rx::Context) {
-        while let Ok(d) = cx.local.usart.read() {
-            parse::spawn(d).unwrap();
-        }
+        dispatch(cx.shared.link);
     }
 
-    /// This lower priority software task handles the message.
-    /// With a terrible function to dim the light.
-    /// Terrible but enough for proof of concept.
-    #[task(capacity = 16, priority = 1, local=[pwm_channel, buf])]
-    fn parse(cx: parse::Context, d: u8) {
-        let _ = cx.local.buf.push(d);
+    /// The ring is half or completely full, while the line is still busy.
+    #[task(binds=DMA2_STREAM2, priority = 2, shared=[link])]
+    fn ring_rx(cx: ring_rx::Context) {
+        dispatch(cx.shared.link);
+    }
 
-        // 0 is the terminating byte of the Postcard serializer
-        if d == 0 {
-            if let Ok(command) = from_bytes_cobs(cx.local.buf) {
-                defmt::debug!("Received complete command: {:?}.", command);
-                match command {
-                    Command::On => {
-                        cx.local
-                            .pwm_channel
-                            .set_duty(cx.local.pwm_channel.get_max_duty());
-                    }
-                    Command::Off => {
-                        cx.local.pwm_channel.set_duty(0);
-                    }
-                    Command::Pwm(level) => {
-                        // 24 : this magic number corresponds to the max duty,
-                        // nothing to worry about here.
-                        // And division by zero is bad for health.
-                        // the sent value is always max == 255
-                        let max = cx.local.pwm_channel.get_max_duty();
-                        if level > 10 && level < 250 {
-                            defmt::info!(
-                                "Duty = {:?}/{:?}",
-                                cx.local.pwm_channel.get_max_duty(),
-                                level * 8
-                            );
-                            cx.local.pwm_channel.set_duty(max / level)
-                        } else if level >= 250 {
-                            cx.local.pwm_channel.set_duty(0);
-                        } else if level < 10 {
-                            cx.local.pwm_channel.set_duty(max);
-                        }
+    fn dispatch(link: &mut Receiver) {
+        link.rx().on_interrupt();
+        loop {
+            match link.receive::<Command>(monotonics::now()) {
+                Ok(command) => {
+                    if parse::spawn(command).is_err() {
+                        defmt::warn!("Too many commands, {:?} is dropped.", command);
                     }
                 }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped())
+                }
+            }
+        }
+    }
+
+    /// This lower priority software task handles the message.
+    /// The level is the brightness seen, `CURVE` turns it into a duty.
+    /// A command in the middle of a fade takes over from where the led is.
+    #[task(capacity = 4, priority = 1, shared=[dimmer, led])]
+    fn parse(cx: parse::Context, command: Command) {
+        defmt::debug!("Received complete command: {:?}.", command);
+        let dimmer = cx.shared.dimmer;
+        let was_fading = dimmer.is_fading();
+        match dimmer.apply(command, monotonics::now()) {
+            Some(duty) => {
+                defmt::info!("Duty = {:?}/{:?}", duty, dimmer.max());
+                cx.shared.led.set_duty(duty);
             }
-            //Clear också om from_bytes failar
-            cx.local.buf.clear();
-        };
+            None => defmt::debug!("Ignoring {:?}, no change here.", command),
+        }
+        // The fade already running steps the new one
+        if dimmer.is_fading() && !was_fading {
+            fade::spawn_after(FADE_STEP).ok();
+        }
+    }
+
+    /// Steps the duty of the led every `FADE_STEP` until the fade is over.
+    #[task(priority = 1, shared=[dimmer, led])]
+    fn fade(cx: fade::Context) {
+        let dimmer = cx.shared.dimmer;
+        let duty = dimmer.step(monotonics::now());
+        cx.shared.led.set_duty(duty);
+        if dimmer.is_fading() {
+            fade::spawn_after(FADE_STEP).ok();
+        } else {
+            defmt::debug!("Fade over.");
+        }
     }
 }
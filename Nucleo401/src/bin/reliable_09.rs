@@ -0,0 +1,166 @@
+//! Same as `interval_08`, but every command is acknowledged to the nRF52.
+//! A command that is received twice (because the acknowledgement got lost) is applied once.
+
+#![no_main]
+#![no_std]
+
+use nucleis as _; // global logger + panicking-behavior + memory layout
+
+#[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
+mod app {
+    use appcore::{
+        blinker::Lamp,
+        board::show,
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport},
+        txqueue::{Overflow, TxQueue},
+    };
+    use nucleis::{
+        board::{Led, Link, Mono, Nucleo},
+        dma::RX_RING,
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        reliable::{Receiver, Reply, Request},
+        Command, Duration,
+    };
+    use stm32f4xx_hal::{nb, pac::USART1, prelude::*, serial::Tx};
+
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
+
+    #[monotonic(binds = TIM5, default = true)]
+    type Monotonic = Mono;
+
+    #[shared]
+    struct Shared {
+        #[lock_free]
+        lamp: Lamp,
+        // Both interrupts of the UART run at the same priority, and only them use the
+        // link, the receiver and the queue.
+        #[lock_free]
+        link: Link,
+        #[lock_free]
+        receiver: Receiver,
+        // The nRF52 sends again what is not acknowledged, the oldest reply can go
+        #[lock_free]
+        tx_queue: TxQueue<Reply, 4>,
+    }
+
+    #[local]
+    struct Local {
+        // pwm has now the led, they are inseparable!
+        led: Led,
+    }
+
+    // The DMA writes in the ring for the whole program, it is static when initiated there
+    #[init(local=[rx_ring: [u8; RX_RING] = [0; RX_RING]])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        defmt::info!("init");
+        // Clocks, pwm, UART and its DMA, see `nucleis::board`
+        let (board, mono) = Nucleo::new(cx.device, cx.local.rx_ring);
+        blink::spawn().ok();
+        (
+            Shared {
+                lamp: Lamp::new(board.led.0.get_max_duty()),
+                link: SerialTransport::new(board.tx, board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+                receiver: Receiver::new(),
+                tx_queue: TxQueue::new(Overflow::DropOldest),
+            },
+            Local { led: board.led },
+            init::Monotonics(mono),
+        )
+    }
+
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
+        loop {
+            sleep(cx.local.meter, monotonics::now);
+        }
+    }
+
+    // The line went idle after some bytes, or the transmit register is empty again
+    #[task(binds=USART1, priority = 2, shared=[link, receiver, tx_queue])]
+    fn usart1(cx: usart1::Context) {
+        dispatch(cx.shared.link, cx.shared.receiver, cx.shared.tx_queue);
+    }
+
+    // The ring is half or completely full, while the line is still busy
+    #[task(binds=DMA2_STREAM2, priority = 2, shared=[link, receiver, tx_queue])]
+    fn ring_rx(cx: ring_rx::Context) {
+        dispatch(cx.shared.link, cx.shared.receiver, cx.shared.tx_queue);
+    }
+
+    /// A hardware task must do only the dispatching: whole requests are answered right
+    /// away, their command goes to `apply`.
+    fn dispatch(link: &mut Link, receiver: &mut Receiver, queue: &mut TxQueue<Reply, 4>) {
+        link.rx().on_interrupt();
+        loop {
+            let reply = match link.receive::<Request>(monotonics::now()) {
+                Ok(request) => {
+                    defmt::debug!("Received complete request: {:?}.", request);
+                    let (reply, command) = receiver.receive(request);
+                    match command {
+                        None => defmt::info!(
+                            "Already applied, {} duplicates so far.",
+                            receiver.duplicates
+                        ),
+                        Some(command) => {
+                            if apply::spawn(command).is_err() {
+                                defmt::warn!("Too many commands, {:?} is dropped.", command);
+                            }
+                        }
+                    }
+                    reply
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped());
+                    // The nRF52 sends it again without waiting for its timeout
+                    receiver.reject()
+                }
+            };
+            if !queue.push(reply) {
+                defmt::warn!("TX queue full, {:?}", queue.stats);
+            }
+        }
+        transmit(link.tx(), queue);
+    }
+
+    /// Fills the transmit register, byte by byte, until the queue is empty.
+    fn transmit(tx: &mut Tx<USART1>, queue: &mut TxQueue<Reply, 4>) {
+        loop {
+            // Back here when the register is empty again
+            if !tx.is_tx_empty() {
+                tx.listen();
+                break;
+            }
+            match queue.next_byte() {
+                Some(byte) => tx.write(byte).unwrap(),
+                None => {
+                    tx.unlisten();
+                    break;
+                }
+            }
+        }
+    }
+
+    // The lower priority software task handles the command
+    #[task(capacity = 4, priority = 1, shared=[lamp])]
+    fn apply(cx: apply::Context, command: Command) {
+        cx.shared.lamp.apply(command);
+    }
+
+    /// This task is setting the light on or off with an interval.
+    /// It reads the brightness (a shared resource) and spawn itself after a delay
+    /// that you decide with the nRF52 buttons!
+    #[task(shared=[lamp], local=[led])]
+    fn blink(cx: blink::Context) {
+        let blink = cx.shared.lamp.blink();
+        show(cx.local.led, blink);
+        blink::spawn_after(blink.next_in).ok();
+    }
+}
@@ -7,12 +7,15 @@ use nucleis as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = stm32f4xx_hal::pac, dispatchers = [USART2])]
 mod app {
 
+    use appcore::sleep::SleepMeter;
+    use cortex_m::peripheral::NVIC;
+    use nucleis::power::{debug_in_sleep, wait_for_event, wake_on_pending, SLEEP_REPORT_PERIOD};
     use stm32f4xx_hal::{
         gpio::gpioa::{PA10, PA9},
         gpio::{Alternate, PushPull},
-        pac::USART1,
+        pac::{Interrupt, USART1},
         prelude::*,
-        serial::{config::Config as UartConfig, Serial},
+        serial::{config::Config as UartConfig, Event, Serial},
         timer::{monotonic::MonoTimer, Timer},
     };
     // We create a generic serial type that supports u8 and u16 (albeit the later is not used). 7 is AF7 which is alternative function!
@@ -32,9 +35,10 @@ mod app {
     }
 
     #[init]
-    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+    fn init(mut cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
         let device = cx.device;
+        debug_in_sleep(&device.DBGMCU);
 
         // Set up the system clocks
         let rcc = device.RCC.constrain();
@@ -45,24 +49,34 @@ mod app {
         let usart_rx = gpioa.pa10.into_alternate();
         let usart_tx = gpioa.pa9.into_alternate();
 
-        let usart = Serial::new(
+        let mut usart = Serial::new(
             device.USART1,
             (usart_tx, usart_rx),
             UartConfig::default().baudrate(9600.bps()),
             &clocks,
         )
         .unwrap();
+        // USART1 has no task: a byte only pends it, and that wakes idle
+        usart.listen(Event::Rxne);
+        wake_on_pending(&mut cx.core.SCB);
 
         (Shared {}, Local { usart }, init::Monotonics(mono))
     }
 
-    /// Idle is always on, and is monitoring received bytes.
+    /// Idle is always on, and is monitoring received bytes. It sleeps until one arrives.
     /// When 4 bytes arrived, we convert to a beer emoji with core::str::from_utf8()
     /// And then, a beer is send back, byte by byte!
-    #[idle(local=[usart, buf: [u8;4] = [0;4], counter: usize = 0])]
+    #[idle(local=[
+        usart,
+        buf: [u8;4] = [0;4],
+        counter: usize = 0,
+        meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)
+    ])]
     fn idle(cx: idle::Context) -> ! {
         loop {
-            if let Ok(d) = cx.local.usart.read() {
+            // Unpended before reading, a byte arriving after that still wakes us
+            NVIC::unpend(Interrupt::USART1);
+            while let Ok(d) = cx.local.usart.read() {
                 cx.local.buf[*cx.local.counter] = d;
                 *cx.local.counter += 1;
 
@@ -78,6 +92,7 @@ mod app {
                 defmt::info!("Writing the byte back: cx.local.usart.write(d)");
                 let _ = cx.local.usart.write(d);
             }
+            wait_for_event(cx.local.meter, monotonics::now);
         }
     }
 }
@@ -0,0 +1,181 @@
+//! The STM32F401 Nucleo, set up the way the examples use it.
+
+use appcore::{board::Board, transport::SerialTransport};
+use stm32f4xx_hal::{
+    dma::StreamsTuple,
+    gpio::{gpioc::PC13, Edge, Input, PullUp},
+    hal::{digital::v2::OutputPin, PwmPin},
+    pac::{self, TIM2, TIM3, TIM5, USART1},
+    prelude::*,
+    pwm::PwmChannel,
+    serial::{
+        config::{Config as UartConfig, DmaConfig},
+        Serial, Tx,
+    },
+    timer::{monotonic::MonoTimer, Timer, C1, C2, C3},
+};
+
+use crate::{
+    dma::{DmaRx, RX_RING},
+    power::debug_in_sleep,
+};
+
+pub type Mono = MonoTimer<TIM5, 1_000_000>;
+
+/// Frames over USART1, see `appcore::transport`.
+pub type Link = SerialTransport<Tx<USART1>, DmaRx>;
+
+/// The green led LD2 on PA5. It is also the pwm channel: the Nucleo has no other led
+/// of its own we can dim, so `led()` and `pwm()` are the same.
+pub struct Led(pub PwmChannel<TIM2, C1>);
+
+impl OutputPin for Led {
+    type Error = core::convert::Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_duty(0);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_duty(self.0.get_max_duty());
+        Ok(())
+    }
+}
+
+impl PwmPin for Led {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.0.disable()
+    }
+
+    fn enable(&mut self) {
+        self.0.enable()
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.0.get_duty()
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        self.0.get_max_duty()
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.0.set_duty(duty)
+    }
+}
+
+/// A common cathode RGB led, through its resistors, on the pwm channels of TIM3: red
+/// on PA6 (D12), green on PA7 (D11) and blue on PB0 (A3). TIM3 counts from the same
+/// clock as TIM2, the maximum duty is the one of `Led`.
+pub struct Rgb {
+    pub red: PwmChannel<TIM3, C1>,
+    pub green: PwmChannel<TIM3, C2>,
+    pub blue: PwmChannel<TIM3, C3>,
+}
+
+impl Rgb {
+    /// Sets the duty of `color`: 0 for red, 1 for green and 2 for blue.
+    pub fn set_duty(&mut self, color: usize, duty: u16) {
+        match color {
+            0 => self.red.set_duty(duty),
+            1 => self.green.set_duty(duty),
+            2 => self.blue.set_duty(duty),
+            _ => {}
+        }
+    }
+}
+
+pub struct Nucleo {
+    pub led: Led,
+    pub rgb: Rgb,
+    /// The blue user button B1, on PC13. A press fires EXTI15_10.
+    pub buttons: [PC13<Input<PullUp>>; 1],
+    /// USART1 on PA9 (TX) and PA10 (RX), received by DMA, see `nucleis::dma`.
+    pub tx: Tx<USART1>,
+    pub rx: DmaRx,
+}
+
+impl Nucleo {
+    /// Clocks at 48 MHz, the pwms at 20 kHz and enabled, the UART at 9600 bauds. The DMA
+    /// receives in `rx_ring` for the whole program, hence the `'static`. The monotonic
+    /// on TIM5 comes apart, it is RTIC's.
+    pub fn new(mut device: pac::Peripherals, rx_ring: &'static mut [u8; RX_RING]) -> (Self, Mono) {
+        debug_in_sleep(&device.DBGMCU);
+        let rcc = device.RCC.constrain();
+        let clocks = rcc.cfgr.sysclk(48.mhz()).require_pll48clk().freeze();
+
+        let mono = Timer::new(device.TIM5, &clocks).monotonic();
+        let gpioa = device.GPIOA.split();
+        let gpiob = device.GPIOB.split();
+        let gpioc = device.GPIOC.split();
+        let usart_rx = gpioa.pa10.into_alternate();
+        let usart_tx = gpioa.pa9.into_alternate();
+        let led = gpioa.pa5.into_alternate();
+        let mut pwm_channel = Timer::new(device.TIM2, &clocks).pwm(led, 20.khz());
+        pwm_channel.enable();
+        let rgb_pins = (
+            gpioa.pa6.into_alternate(),
+            gpioa.pa7.into_alternate(),
+            gpiob.pb0.into_alternate(),
+        );
+        let (mut red, mut green, mut blue) =
+            Timer::new(device.TIM3, &clocks).pwm(rgb_pins, 20.khz());
+        red.enable();
+        green.enable();
+        blue.enable();
+        let mut button = gpioc.pc13.into_pull_up_input();
+        let mut syscfg = device.SYSCFG.constrain();
+        button.make_interrupt_source(&mut syscfg);
+        button.enable_interrupt(&mut device.EXTI);
+        button.trigger_on_edge(&mut device.EXTI, Edge::Falling);
+
+        let usart = Serial::new(
+            device.USART1,
+            (usart_tx, usart_rx),
+            UartConfig {
+                dma: DmaConfig::Rx,
+                ..UartConfig::default().baudrate(9600.bps())
+            },
+            &clocks,
+        )
+        .unwrap();
+        let (tx, rx) = usart.split();
+        let rx = DmaRx::new(StreamsTuple::new(device.DMA2).2, rx, rx_ring);
+
+        let nucleo = Nucleo {
+            led: Led(pwm_channel),
+            rgb: Rgb { red, green, blue },
+            buttons: [button],
+            tx,
+            rx,
+        };
+        (nucleo, mono)
+    }
+}
+
+impl Board for Nucleo {
+    type Led = Led;
+    type Button = PC13<Input<PullUp>>;
+    type Tx = Tx<USART1>;
+    type Rx = DmaRx;
+    type Pwm = Led;
+
+    fn led(&mut self) -> &mut Led {
+        &mut self.led
+    }
+
+    fn buttons(&mut self) -> &mut [Self::Button] {
+        &mut self.buttons
+    }
+
+    fn uart(&mut self) -> (&mut Self::Tx, &mut Self::Rx) {
+        (&mut self.tx, &mut self.rx)
+    }
+
+    fn pwm(&mut self) -> &mut Led {
+        &mut self.led
+    }
+}
@@ -0,0 +1,96 @@
+//! USART1 reception by DMA, in circles: the interrupts only say that there is
+//! something to read, not one interrupt per byte.
+
+use appcore::{ring::Ring, transport::Backlog};
+use stm32f4xx_hal::{
+    dma::{config::DmaConfig, traits::Stream, PeripheralToMemory, Stream2, Transfer},
+    hal::serial,
+    nb,
+    pac::{DMA2, USART1},
+    serial::{Error, Rx},
+};
+
+/// Bytes the DMA can write before we read them: at 9600 bauds the half transfer
+/// interrupt fires every ~33 ms if the line is never idle.
+pub const RX_RING: usize = 64;
+
+type RxTransfer =
+    Transfer<Stream2<DMA2>, Rx<USART1>, PeripheralToMemory, &'static mut [u8; RX_RING], 4>;
+
+/// Reads what the DMA wrote in the ring since last time, one byte at a time.
+/// `on_interrupt` must run on both USART1 (idle line) and DMA2_STREAM2 (half and full
+/// transfer) interrupts.
+pub struct DmaRx {
+    transfer: RxTransfer,
+    // The transfer owns the buffer, the DMA writes it behind our back
+    ring: *const u8,
+    read: Ring<RX_RING>,
+}
+
+// The pointer is only read, in whichever task owns the `DmaRx`
+unsafe impl Send for DmaRx {}
+
+impl DmaRx {
+    /// Starts the transfer. USART1 must have its DMA request on (`DmaConfig::Rx` in
+    /// the serial config).
+    pub fn new(
+        stream: Stream2<DMA2>,
+        mut rx: Rx<USART1>,
+        ring: &'static mut [u8; RX_RING],
+    ) -> Self {
+        let ptr = ring.as_ptr();
+        rx.listen_idle();
+        let mut transfer = Transfer::init_peripheral_to_memory(
+            stream,
+            rx,
+            ring,
+            None,
+            DmaConfig::default()
+                .memory_increment(true)
+                .half_transfer_interrupt(true)
+                .transfer_complete_interrupt(true),
+        );
+        // The HAL has no circular mode: once the end of the buffer is reached, start over
+        // SAFETY: the stream is ours and still disabled
+        unsafe { (*DMA2::ptr()).st[2].cr.modify(|_, w| w.circ().enabled()) };
+        transfer.start(|_| {});
+        DmaRx {
+            transfer,
+            ring: ptr,
+            read: Ring::new(),
+        }
+    }
+
+    /// Clears the flags of both interrupts, the bytes stay in the ring.
+    pub fn on_interrupt(&mut self) {
+        self.transfer.clear_half_transfer_interrupt();
+        self.transfer.clear_transfer_complete_interrupt();
+        // Like `Rx::clear_idle_interrupt`, the transfer owns the receiver. Only when the
+        // line is idle: the data register is the DMA's while bytes come.
+        unsafe {
+            if (*USART1::ptr()).sr.read().idle().bit_is_set() {
+                let _ = (*USART1::ptr()).dr.read();
+            }
+        }
+    }
+}
+
+impl Backlog for DmaRx {
+    fn backlog(&self) -> usize {
+        self.read
+            .pending(<Stream2<DMA2> as Stream>::get_number_of_transfers())
+    }
+}
+
+impl serial::Read<u8> for DmaRx {
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        let remaining = <Stream2<DMA2> as Stream>::get_number_of_transfers();
+        match self.read.next(remaining) {
+            // SAFETY: `at` is in the ring, which lives forever
+            Some(at) => Ok(unsafe { core::ptr::read_volatile(self.ring.add(at)) }),
+            None => Err(nb::Error::WouldBlock),
+        }
+    }
+}
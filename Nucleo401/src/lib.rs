@@ -6,6 +6,10 @@ use defmt_rtt as _; // global logger
 
 use stm32f4xx_hal as _; // memory layout
 
+pub mod board;
+pub mod dma;
+pub mod power;
+
 use panic_probe as _;
 
 // same panicking *behavior* as `panic-probe` but doesn't print a panic message
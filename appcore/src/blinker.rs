@@ -0,0 +1,494 @@
+//! The Nucleo side of `interval_08` and `reliable_09`: a led blinking with the
+//! brightness and the interval sent by the nRF52, and for `interval_08` a failsafe
+//! blink when the nRF52 goes silent. `interval_08` and the nRF52 `leds_10` blink
+//! several leds, see `channels`, and fade them while they are lit.
+
+use protocol::{
+    clamp_blink_ms,
+    link::{Supervisor, Transition, HEARTBEAT_PERIOD},
+    Command, Duration, Instant,
+};
+
+use crate::{brightness::Curve, channels::for_channel, dimmer::Dimmer, fade::FADE_STEP};
+
+/// The link is down after this many heartbeats were missed.
+pub const MAX_MISSED: u32 = 3;
+/// What the led does while the link is down: a slow and dim blink.
+/// Set the brightness to 0 to turn the led off instead.
+pub const FAILSAFE_BRIGHTNESS: u8 = 64;
+/// As long lit as dark.
+pub const FAILSAFE_MS: u32 = 3000;
+/// How often a steadily lit led looks for a new brightness or blink.
+pub const STEADY_PERIOD: Duration = Duration::millis(100);
+
+/// What the `blink` task must do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Blink {
+    /// Duty to set on the pwm channel.
+    pub duty: u16,
+    /// Time until the next blink.
+    pub next_in: Duration,
+}
+
+/// A led blinking with the last brightness and interval received.
+#[derive(Clone, Copy)]
+pub struct Lamp {
+    /// The brightness seen, out of 255, the one of the end of a fade.
+    brightness: u8,
+    /// The level lit, through the fades, see `blink_at`.
+    dimmer: Dimmer,
+    curve: Curve,
+    max_duty: u16,
+    /// Time lit and time dark, 0 for steadily on.
+    on_ms: u32,
+    off_ms: u32,
+    powered: bool,
+    /// The end of the time lit, while a fade shows during it.
+    lit_until: Option<Instant>,
+}
+
+impl Lamp {
+    /// A lamp at full brightness, on a pwm channel of `max_duty`.
+    pub const fn new(max_duty: u16) -> Self {
+        Lamp {
+            brightness: u8::MAX,
+            dimmer: Dimmer::new(Curve::DEFAULT, max_duty).with_level(u8::MAX),
+            curve: Curve::DEFAULT,
+            max_duty,
+            on_ms: 1000,
+            off_ms: 1000,
+            powered: false,
+            lit_until: None,
+        }
+    }
+
+    /// How the brightness turns into a duty, `Curve::DEFAULT` otherwise.
+    pub const fn with_curve(self, curve: Curve) -> Self {
+        Lamp {
+            curve,
+            dimmer: Dimmer::new(curve, self.max_duty).with_level(self.brightness),
+            ..self
+        }
+    }
+
+    /// Handles `command`, for a lamp without a clock: a fade is a jump.
+    pub fn apply(&mut self, command: Command) {
+        let command = match command {
+            Command::Fade { target, .. } => Command::Pwm(target),
+            _ => command,
+        };
+        // Without a fade, nothing depends on the time
+        self.apply_at(command, Instant::from_ticks(0));
+    }
+
+    /// Handles `command`, received at `now`. A fade goes on lit or dark, `blink_at`
+    /// shows it while the led is lit.
+    pub fn apply_at(&mut self, command: Command, now: Instant) {
+        self.dimmer.apply(command, now);
+        match command {
+            Command::On => self.brightness = u8::MAX,
+            Command::Off => self.brightness = 0,
+            Command::Pwm(level) | Command::Fade { target: level, .. } => self.brightness = level,
+            Command::Interval(sec) => self.set_times(u32::from(sec) * 1000, u32::from(sec) * 1000),
+            Command::Blink { on_ms, off_ms } => self.set_times(on_ms, off_ms),
+            // Nothing to show, or for `pattern::Sequencer`
+            Command::Heartbeat
+            | Command::Play { .. }
+            | Command::Step { .. }
+            | Command::Morse { .. }
+            | Command::Stop => {}
+            // For `channels::for_channel`
+            Command::Channel { .. } | Command::Rgb { .. } => {}
+        }
+    }
+
+    /// The sender clamps them too, this is for the ones that do not.
+    fn set_times(&mut self, on_ms: u32, off_ms: u32) {
+        self.on_ms = clamp_blink_ms(on_ms);
+        self.off_ms = clamp_blink_ms(off_ms);
+    }
+
+    /// Turns the led on if it was off, and the other way around, without a clock: lit
+    /// at the brightness, the end of a fade.
+    pub fn blink(&mut self) -> Blink {
+        self.toggle(
+            self.curve.duty8(self.brightness, self.max_duty),
+            self.on_ms,
+            self.off_ms,
+        )
+    }
+
+    /// The same at `now`, lit at the level of the fade going on. While it fades, the led
+    /// is lit for `FADE_STEP`s until the end of its time lit, a step of the fade each.
+    pub fn blink_at(&mut self, now: Instant) -> Blink {
+        if let Some(until) = self.lit_until {
+            match until.checked_duration_since(now) {
+                Some(left) if left.ticks() > 0 => {
+                    let duty = self.dimmer.step(now);
+                    return Blink {
+                        duty,
+                        next_in: self.fade_step(left),
+                    };
+                }
+                _ => self.lit_until = None,
+            }
+        }
+        let lit = self.dimmer.step(now);
+        let blink = self.toggle(lit, self.on_ms, self.off_ms);
+        // `powered` says the next one lights the led: this one is dark
+        if self.powered || !self.dimmer.is_fading() {
+            return blink;
+        }
+        self.lit_until = Some(now + blink.next_in);
+        Blink {
+            next_in: self.fade_step(blink.next_in),
+            ..blink
+        }
+    }
+
+    /// How long to show a step of the fade, when `left` of the time lit remains.
+    fn fade_step(&self, left: Duration) -> Duration {
+        if self.dimmer.is_fading() {
+            left.min(FADE_STEP)
+        } else {
+            left
+        }
+    }
+
+    fn toggle(&mut self, lit: u16, on_ms: u32, off_ms: u32) -> Blink {
+        self.lit_until = None;
+        if on_ms == 0 || off_ms == 0 {
+            // the blink starts dark once it is back
+            self.powered = false;
+            return Blink {
+                duty: lit,
+                next_in: STEADY_PERIOD,
+            };
+        }
+        let (duty, ms) = if self.powered {
+            (lit, on_ms)
+        } else {
+            (0, off_ms)
+        };
+        self.powered = !self.powered;
+        Blink {
+            duty,
+            next_in: Duration::millis(ms),
+        }
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    /// Milliseconds lit and dark.
+    pub fn times_ms(&self) -> (u32, u32) {
+        (self.on_ms, self.off_ms)
+    }
+}
+
+/// The `Lamp`s of `N` channels, that fall back to the failsafe blink while the link
+/// is down.
+pub struct Blinker<const N: usize = 1> {
+    lamps: [Lamp; N],
+    pub link: Supervisor,
+}
+
+impl Blinker {
+    /// See `Lamp::new`.
+    pub const fn new(max_duty: u16) -> Self {
+        Blinker::channels(max_duty)
+    }
+}
+
+impl<const N: usize> Blinker<N> {
+    /// `N` lamps on pwm channels of `max_duty`.
+    pub const fn channels(max_duty: u16) -> Self {
+        Blinker {
+            lamps: [Lamp::new(max_duty); N],
+            link: Supervisor::new(HEARTBEAT_PERIOD, MAX_MISSED),
+        }
+    }
+
+    pub const fn with_curve(mut self, curve: Curve) -> Self {
+        let mut channel = 0;
+        while channel < N {
+            self.lamps[channel] = self.lamps[channel].with_curve(curve);
+            channel += 1;
+        }
+        self
+    }
+
+    /// Handles a command received at `at`, for the channels it is for.
+    /// Returns the change of the link, if this is the first frame in a while.
+    pub fn on_command(&mut self, command: Command, at: Instant) -> Option<Transition> {
+        let transition = self.link.on_frame(at);
+        for (channel, lamp) in self.lamps.iter_mut().enumerate() {
+            if let Some(command) = for_channel(command, channel) {
+                lamp.apply_at(command, at);
+            }
+        }
+        transition
+    }
+
+    /// Turns the led of channel 0 on if it was off, and the other way around, at `now`,
+    /// see `Lamp::blink_at`.
+    pub fn blink(&mut self, now: Instant) -> Blink {
+        self.blink_channel(0, now)
+    }
+
+    /// The same for the led of `channel`. While the link is down, channel 0 blinks
+    /// the failsafe instead and the others stay dark.
+    pub fn blink_channel(&mut self, channel: usize, now: Instant) -> Blink {
+        let lamp = &mut self.lamps[channel];
+        match (self.link.is_up(), channel) {
+            (true, _) => lamp.blink_at(now),
+            (false, 0) => {
+                let lit = lamp.curve.duty8(FAILSAFE_BRIGHTNESS, lamp.max_duty);
+                lamp.toggle(lit, FAILSAFE_MS, FAILSAFE_MS)
+            }
+            (false, _) => lamp.toggle(0, 0, 0),
+        }
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.lamps[0].brightness()
+    }
+
+    pub fn times_ms(&self) -> (u32, u32) {
+        self.lamps[0].times_ms()
+    }
+
+    pub fn lamp(&self, channel: usize) -> &Lamp {
+        &self.lamps[channel]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::{ChannelCommand, Easing, MAX_BLINK_MS, MIN_BLINK_MS};
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    const MAX: u16 = 2400;
+
+    #[test]
+    fn failsafe_until_the_first_frame() {
+        let mut blinker = Blinker::new(MAX);
+        assert_eq!(
+            blinker.blink(at(0)),
+            Blink {
+                duty: 0,
+                next_in: Duration::millis(FAILSAFE_MS)
+            }
+        );
+        assert_eq!(
+            blinker.blink(at(0)).duty,
+            Curve::DEFAULT.duty8(FAILSAFE_BRIGHTNESS, MAX)
+        );
+    }
+
+    #[test]
+    fn blinks_with_the_commands() {
+        let mut blinker = Blinker::new(MAX);
+        assert_eq!(
+            blinker.on_command(Command::Interval(2), at(0)),
+            Some(Transition::Up)
+        );
+        assert_eq!(blinker.on_command(Command::Pwm(100), at(10)), None);
+        assert_eq!(
+            blinker.blink(at(0)),
+            Blink {
+                duty: 0,
+                next_in: Duration::millis(2000)
+            }
+        );
+        assert_eq!(
+            blinker.blink(at(0)),
+            Blink {
+                duty: 259,
+                next_in: Duration::millis(2000)
+            }
+        );
+        blinker.on_command(Command::On, at(20));
+        blinker.blink(at(0));
+        assert_eq!(blinker.blink(at(0)).duty, MAX);
+    }
+
+    #[test]
+    fn lamp_without_a_link() {
+        let mut lamp = Lamp::new(MAX);
+        assert_eq!(lamp.blink().duty, 0);
+        // full brightness until told otherwise
+        assert_eq!(lamp.blink().duty, MAX);
+        lamp.apply(Command::Off);
+        lamp.apply(Command::Interval(0));
+        lamp.apply(Command::Heartbeat);
+        assert_eq!(
+            lamp.blink(),
+            Blink {
+                duty: 0,
+                next_in: STEADY_PERIOD
+            }
+        );
+        assert_eq!(lamp.blink().duty, 0);
+        assert_eq!((lamp.brightness(), lamp.times_ms()), (0, (0, 0)));
+        lamp.apply(Command::Pwm(150));
+        assert_eq!(lamp.brightness(), 150);
+
+        let mut linear = Lamp::new(MAX).with_curve(Curve::Gamma(10));
+        linear.apply(Command::Pwm(128));
+        linear.blink();
+        assert_eq!(linear.blink().duty, 1205);
+    }
+
+    #[test]
+    fn heartbeat_keeps_the_link_up() {
+        let mut blinker = Blinker::new(0);
+        blinker.on_command(Command::Off, at(0));
+        for ms in (500..5000).step_by(500) {
+            blinker.on_command(Command::Heartbeat, at(ms));
+            assert_eq!(blinker.link.poll(at(ms + 100)), None);
+        }
+        assert_eq!(blinker.link.poll(at(6600)), Some(Transition::Down));
+        assert_eq!(blinker.blink(at(0)).next_in, Duration::millis(FAILSAFE_MS));
+        // the commands still count while the link is down, they show once it is back
+        blinker.on_command(Command::Interval(5), at(7000));
+        assert_eq!(
+            (blinker.brightness(), blinker.times_ms()),
+            (0, (5000, 5000))
+        );
+        assert_eq!(blinker.blink(at(0)).next_in, Duration::millis(5000));
+    }
+
+    #[test]
+    fn every_channel_blinks_its_own_way() {
+        let mut blinker = Blinker::<4>::channels(MAX);
+        blinker.on_command(
+            Command::Channel {
+                channel: 2,
+                command: ChannelCommand::Blink {
+                    on_ms: 100,
+                    off_ms: 300,
+                },
+            },
+            at(0),
+        );
+        blinker.on_command(
+            Command::Rgb {
+                red: 0,
+                green: 128,
+                blue: 255,
+            },
+            at(10),
+        );
+        blinker.on_command(Command::Interval(2), at(20));
+        let brightness = |blinker: &Blinker<4>| [0, 1, 2, 3].map(|c| blinker.lamp(c).brightness());
+        assert_eq!(brightness(&blinker), [255, 0, 128, 255]);
+        assert_eq!(blinker.lamp(2).times_ms(), (100, 300));
+        assert_eq!(blinker.times_ms(), (2000, 2000));
+        assert_eq!(blinker.lamp(3).times_ms(), (1000, 1000));
+        // dark first, then lit
+        let blinks = |blinker: &mut Blinker<4>| {
+            [0, 1, 2, 3].map(|c| {
+                let blink = blinker.blink_channel(c, at(0));
+                (blink.duty, blink.next_in.to_millis())
+            })
+        };
+        assert_eq!(
+            blinks(&mut blinker),
+            [(0, 2000), (0, 1000), (0, 300), (0, 1000)]
+        );
+        let green = Curve::DEFAULT.duty8(128, MAX);
+        assert_eq!(
+            blinks(&mut blinker),
+            [(MAX, 2000), (0, 1000), (green, 100), (MAX, 1000)]
+        );
+    }
+
+    #[test]
+    fn only_channel_0_shows_the_failsafe() {
+        let mut blinker = Blinker::<2>::channels(MAX);
+        blinker.blink_channel(0, at(0));
+        assert_eq!(
+            blinker.blink_channel(0, at(0)).duty,
+            Curve::DEFAULT.duty8(FAILSAFE_BRIGHTNESS, MAX)
+        );
+        for _ in 0..2 {
+            assert_eq!(
+                blinker.blink_channel(1, at(0)),
+                Blink {
+                    duty: 0,
+                    next_in: STEADY_PERIOD
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn milliseconds_on_and_off() {
+        let mut lamp = Lamp::new(MAX);
+        let blinks = |lamp: &mut Lamp| [lamp.blink(), lamp.blink()].map(|b| (b.duty, b.next_in));
+        lamp.apply(Command::blink(50, 950));
+        assert_eq!(
+            blinks(&mut lamp),
+            [(0, Duration::millis(950)), (MAX, Duration::millis(50))]
+        );
+        // steadily on, then blinking again from the dark
+        lamp.apply(Command::Blink {
+            on_ms: 250,
+            off_ms: 0,
+        });
+        assert_eq!(blinks(&mut lamp), [(MAX, STEADY_PERIOD); 2]);
+        lamp.apply(Command::Blink {
+            on_ms: 1,
+            off_ms: u32::MAX,
+        });
+        assert_eq!(lamp.times_ms(), (MIN_BLINK_MS, MAX_BLINK_MS));
+        assert_eq!(
+            blinks(&mut lamp),
+            [
+                (0, Duration::millis(MAX_BLINK_MS)),
+                (MAX, Duration::millis(MIN_BLINK_MS))
+            ]
+        );
+    }
+
+    #[test]
+    fn fades_while_lit() {
+        let mut blinker = Blinker::<2>::channels(MAX).with_curve(Curve::Gamma(10));
+        let to_1 = |command: ChannelCommand| Command::Channel {
+            channel: 1,
+            command,
+        };
+        for command in [
+            ChannelCommand::Blink {
+                on_ms: 500,
+                off_ms: 500,
+            },
+            ChannelCommand::Off,
+            ChannelCommand::Fade {
+                target: 255,
+                duration_ms: 1000,
+                easing: Easing::Linear,
+            },
+        ] {
+            blinker.on_command(to_1(command), at(0));
+        }
+        let mut blink = |ms| {
+            let blink = blinker.blink_channel(1, at(ms));
+            (blink.duty, blink.next_in.to_millis())
+        };
+        assert_eq!(blink(0), (0, 500));
+        // a step of the fade at a time while lit, until the end of the time lit
+        assert_eq!(blink(500), (MAX / 2, 10));
+        assert_eq!(blink(510), (MAX / 100 * 51, 10));
+        assert_eq!(blink(995), (MAX / 200 * 199, 5));
+        assert_eq!(blink(1000), (0, 500));
+        assert_eq!(blink(1500), (MAX, 500));
+        assert_eq!(blinker.lamp(0).brightness(), u8::MAX);
+    }
+}
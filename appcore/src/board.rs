@@ -0,0 +1,83 @@
+//! What the examples need from a board, whichever it is: the nRF52840 DK, the
+//! Nucleo, or the mock of the `host` tests.
+//!
+//! The boards implement it in `nrfie::board` and `nucleis::board`. An RTIC app keeps
+//! the board in one resource when its tasks can share it, and calls the helpers below
+//! with it. When the parts go to different tasks, like the UART to a receive interrupt,
+//! the helpers that take a part do the same.
+
+use embedded_hal::{
+    digital::v2::{InputPin, OutputPin},
+    serial, PwmPin,
+};
+use protocol::{frame, Instant, MAX_FRAME_LEN};
+use serde::Serialize;
+
+use crate::{
+    blinker::Blink,
+    gesture::{Button, Gesture},
+};
+
+/// The timer is not a part: RTIC owns the monotonic, and the helpers take `now` from it.
+pub trait Board {
+    type Led: OutputPin;
+    type Button: InputPin;
+    type Tx: serial::Write<u8>;
+    type Rx: serial::Read<u8>;
+    type Pwm: PwmPin<Duty = u16>;
+
+    /// The user led, on or off.
+    fn led(&mut self) -> &mut Self::Led;
+    /// The user buttons, in the order of their labels.
+    fn buttons(&mut self) -> &mut [Self::Button];
+    /// The UART that goes to the other board, at 9600 bauds.
+    fn uart(&mut self) -> (&mut Self::Tx, &mut Self::Rx);
+    /// A pwm channel on a led, to dim it.
+    fn pwm(&mut self) -> &mut Self::Pwm;
+}
+
+/// The buttons pull their pin to the ground when pressed.
+pub fn is_pressed<B: InputPin>(button: &B) -> bool {
+    button.is_low().unwrap_or(false)
+}
+
+/// Reads the first buttons of `board` and polls their gestures with the levels, see
+/// `gesture::Button::poll`.
+pub fn read_gestures<B: Board, const N: usize>(
+    board: &mut B,
+    buttons: &mut [Button; N],
+    now: Instant,
+) -> [Option<Gesture>; N] {
+    poll_buttons(board.buttons(), buttons, now)
+}
+
+/// Same as `read_gestures`, with the pins moved out of the board.
+pub fn poll_buttons<P: InputPin, const N: usize>(
+    pins: &[P],
+    buttons: &mut [Button; N],
+    now: Instant,
+) -> [Option<Gesture>; N] {
+    let mut gestures = [None; N];
+    for ((button, pin), gesture) in buttons.iter_mut().zip(pins).zip(&mut gestures) {
+        *gesture = button.poll(is_pressed(pin), now);
+    }
+    gestures
+}
+
+/// Writes `msg` as one frame, waiting for the UART to take every byte.
+pub fn write_frame<W: serial::Write<u8>, T: Serialize>(
+    tx: &mut W,
+    msg: &T,
+) -> Result<(), W::Error> {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    let data = frame::encode(msg, &mut buf).expect("every message fits in MAX_FRAME_LEN");
+    for &b in data.iter() {
+        nb::block!(tx.write(b))?;
+    }
+    nb::block!(tx.flush())
+}
+
+/// Sets the duty of `blink` on the pwm channel.
+pub fn show<P: PwmPin<Duty = u16>>(pwm: &mut P, blink: Blink) {
+    pwm.set_duty(blink.duty.min(pwm.get_max_duty()));
+}
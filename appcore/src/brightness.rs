@@ -0,0 +1,122 @@
+//! How bright the led looks, to the duty of its pwm channel.
+//!
+//! The eye sees small duties much brighter than they are: half the duty looks nearly
+//! as bright as the whole. A `Curve` spreads the perceived levels evenly on the duty
+//! of the channel, whatever its maximum, so whatever the frequency of the pwm.
+
+/// From a perceived level to a fraction of the maximum duty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Curve {
+    /// `level ^ gamma`, the gamma in tenths: 22 is the usual 2.2, 10 is linear.
+    Gamma(u8),
+    /// The lightness L* of CIE 1931, the level being L* from 0 to 100.
+    Cie1931,
+}
+
+impl Curve {
+    pub const DEFAULT: Curve = Curve::Cie1931;
+
+    /// The duty, out of `max`, that looks like `level` out of `u16::MAX`.
+    pub fn duty(self, level: u16, max: u16) -> u16 {
+        match level {
+            0 => 0,
+            u16::MAX => max,
+            _ => {
+                let x = f64::from(level) / f64::from(u16::MAX);
+                let y = match self {
+                    Curve::Gamma(tenths) => gamma(x, tenths),
+                    Curve::Cie1931 => cie1931(x),
+                };
+                (y * f64::from(max) + 0.5) as u16
+            }
+        }
+    }
+
+    /// The same for a level out of 255, like the one of `Command::Pwm`.
+    pub fn duty8(self, level: u8, max: u16) -> u16 {
+        // 255 * 257 is u16::MAX
+        self.duty(u16::from(level) * 257, max)
+    }
+}
+
+fn powi(x: f64, n: u8) -> f64 {
+    (0..n).fold(1.0, |power, _| power * x)
+}
+
+/// `x ^ (tenths / 10)` without `powf`, which `core` lacks: the `y` where
+/// `y ^ 10 = x ^ tenths`, by bisection. A higher `x` never gives a lower `y`.
+fn gamma(x: f64, tenths: u8) -> f64 {
+    let target = powi(x, tenths);
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..32 {
+        let mid = (low + high) / 2.0;
+        if powi(mid, 10) <= target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+/// The luminance of the lightness `100 * x`.
+fn cie1931(x: f64) -> f64 {
+    let lightness = 100.0 * x;
+    if lightness <= 8.0 {
+        lightness / 903.3
+    } else {
+        let t = (lightness + 16.0) / 116.0;
+        t * t * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 4] = [
+        Curve::Cie1931,
+        Curve::Gamma(22),
+        Curve::Gamma(10),
+        Curve::Gamma(28),
+    ];
+    /// The maximum duty of the Nucleo at 20 kHz, of the nRF52, and the largest.
+    const MAXES: [u16; 3] = [2400, 32767, u16::MAX];
+
+    #[test]
+    fn end_points() {
+        for curve in CURVES {
+            for max in MAXES {
+                assert_eq!(curve.duty(0, max), 0, "{:?}", curve);
+                assert_eq!(curve.duty(u16::MAX, max), max, "{:?}", curve);
+                assert_eq!(curve.duty8(0, max), 0, "{:?}", curve);
+                assert_eq!(curve.duty8(u8::MAX, max), max, "{:?}", curve);
+            }
+        }
+    }
+
+    #[test]
+    fn monotonic() {
+        for curve in CURVES {
+            for max in MAXES {
+                let mut last = 0;
+                for level in 0..=u16::MAX {
+                    let duty = curve.duty(level, max);
+                    assert!(duty >= last, "{:?} {} at {}", curve, max, level);
+                    last = duty;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perceived_half_is_a_small_duty() {
+        // L* = 50 is 18.4 % of the luminance, 0.5 ^ 2.2 is 21.8 %
+        assert_eq!(Curve::Cie1931.duty(u16::MAX / 2, 10_000), 1842);
+        assert_eq!(Curve::Gamma(22).duty(u16::MAX / 2, 10_000), 2176);
+        assert_eq!(Curve::Gamma(10).duty8(128, 255), 128);
+        // the first level out of 255 is still lit on the Nucleo
+        assert!(Curve::DEFAULT.duty8(1, 2400) > 0);
+    }
+}
@@ -0,0 +1,106 @@
+//! The leds of `channels`: LD2 and an RGB led on the Nucleo, the four leds of the
+//! nRF52840 DK. Each one keeps its own brightness, blink and pattern.
+//!
+//! `for_channel` gives each channel the commands that are for it, as the plain
+//! `Command`s its `Lamp` and `Sequencer` already know.
+
+use protocol::Command;
+
+/// The leds of both boards.
+pub const CHANNELS: usize = 4;
+/// The channels of the red, green and blue of `Command::Rgb`.
+pub const RGB: [usize; 3] = [1, 2, 3];
+
+/// What `command` means for `channel`, None when it is for another one.
+///
+/// A plain command is for channel 0, like before there were channels. The steps of
+/// `Pattern::Uploaded` are for all of them: any channel can play them.
+pub fn for_channel(command: Command, channel: usize) -> Option<Command> {
+    match command {
+        Command::Channel {
+            channel: to,
+            command,
+        } => (usize::from(to) == channel).then(|| command.into()),
+        Command::Rgb { red, green, blue } => RGB
+            .iter()
+            .zip([red, green, blue])
+            .find(|&(&rgb, _)| rgb == channel)
+            .map(|(_, level)| Command::Pwm(level)),
+        Command::Step { .. } => Some(command),
+        _ => (channel == 0).then_some(command),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::{ChannelCommand, Pattern};
+    use std::vec::Vec;
+
+    fn spread(command: Command) -> Vec<Option<Command>> {
+        (0..CHANNELS)
+            .map(|channel| for_channel(command, channel))
+            .collect()
+    }
+
+    #[test]
+    fn plain_commands_are_for_channel_0() {
+        assert_eq!(
+            spread(Command::Pwm(7)),
+            [Some(Command::Pwm(7)), None, None, None]
+        );
+        assert_eq!(spread(Command::Stop)[0], Some(Command::Stop));
+        let step = Command::Step {
+            index: 0,
+            level: 255,
+            duration_ms: 100,
+        };
+        assert_eq!(spread(step), [Some(step); CHANNELS]);
+    }
+
+    #[test]
+    fn channel_commands_are_for_theirs_only() {
+        let play = Command::Channel {
+            channel: 2,
+            command: ChannelCommand::Play {
+                pattern: Pattern::Heartbeat,
+                repeat: 3,
+            },
+        };
+        assert_eq!(
+            spread(play),
+            [
+                None,
+                None,
+                Some(Command::Play {
+                    pattern: Pattern::Heartbeat,
+                    repeat: 3
+                }),
+                None
+            ]
+        );
+        let missing = Command::Channel {
+            channel: CHANNELS as u8,
+            command: ChannelCommand::On,
+        };
+        assert_eq!(spread(missing), [None; CHANNELS]);
+    }
+
+    #[test]
+    fn rgb() {
+        let rgb = Command::Rgb {
+            red: 255,
+            green: 128,
+            blue: 0,
+        };
+        assert_eq!(
+            spread(rgb),
+            [
+                None,
+                Some(Command::Pwm(255)),
+                Some(Command::Pwm(128)),
+                Some(Command::Pwm(0))
+            ]
+        );
+    }
+}
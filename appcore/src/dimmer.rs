@@ -0,0 +1,180 @@
+//! The Nucleo side of `pwm_07`: the led is dimmed with the level sent by the nRF52,
+//! right away or with a `Command::Fade`. The lamps of `blinker` light up at its level.
+
+use protocol::{Command, Duration, Instant};
+
+use crate::{brightness::Curve, fade::Fade};
+
+/// The led of a pwm channel of `max` duty, and the fade it is going through.
+#[derive(Clone, Copy)]
+pub struct Dimmer {
+    curve: Curve,
+    max: u16,
+    /// The brightness seen out of `u16::MAX`, when not fading.
+    level: u16,
+    fade: Option<Fade>,
+}
+
+impl Dimmer {
+    /// A led that is off. The levels of the commands are the brightness seen, see `Curve`.
+    pub const fn new(curve: Curve, max: u16) -> Self {
+        Dimmer {
+            curve,
+            max,
+            level: 0,
+            fade: None,
+        }
+    }
+
+    /// Starts at `level` out of 255 instead of off.
+    pub const fn with_level(self, level: u8) -> Self {
+        Dimmer {
+            level: level as u16 * 257,
+            ..self
+        }
+    }
+
+    /// Handles `command`, received at `now`: the duty to set right away, if it changes.
+    ///
+    /// A new command in the middle of a fade takes over from where the led is: a
+    /// `Pwm` jumps, a `Fade` starts from the level the led had reached.
+    pub fn apply(&mut self, command: Command, now: Instant) -> Option<u16> {
+        let target = match command {
+            Command::On => u16::MAX,
+            Command::Off => 0,
+            Command::Pwm(level) => u16::from(level) * 257,
+            Command::Fade {
+                target,
+                duration_ms,
+                easing,
+            } => {
+                let from = self.level_at(now);
+                let duration = Duration::millis(duration_ms.into());
+                self.fade = Some(Fade::new(
+                    from,
+                    u16::from(target) * 257,
+                    now,
+                    duration,
+                    easing,
+                ));
+                return Some(self.step(now));
+            }
+            Command::Interval(_)
+            | Command::Blink { .. }
+            | Command::Heartbeat
+            | Command::Play { .. }
+            | Command::Step { .. }
+            | Command::Morse { .. }
+            | Command::Stop
+            | Command::Channel { .. }
+            | Command::Rgb { .. } => return None,
+        };
+        self.fade = None;
+        self.level = target;
+        Some(self.duty())
+    }
+
+    /// The duty at `now`, the last one when the fade is over.
+    pub fn step(&mut self, now: Instant) -> u16 {
+        self.level = self.level_at(now);
+        if self.fade.is_some_and(|fade| fade.is_over(now)) {
+            self.fade = None;
+        }
+        self.duty()
+    }
+
+    /// Steps are still needed.
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    pub fn max(&self) -> u16 {
+        self.max
+    }
+
+    fn level_at(&self, now: Instant) -> u16 {
+        match self.fade {
+            Some(fade) => fade.level(now),
+            None => self.level,
+        }
+    }
+
+    fn duty(&self) -> u16 {
+        self.curve.duty(self.level, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Easing;
+
+    const MAX: u16 = 2400;
+    const CURVE: Curve = Curve::DEFAULT;
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    fn fade(target: u8, duration_ms: u16) -> Command {
+        Command::Fade {
+            target,
+            duration_ms,
+            easing: Easing::Linear,
+        }
+    }
+
+    #[test]
+    fn on_and_off() {
+        let mut dimmer = Dimmer::new(CURVE, MAX);
+        assert_eq!(dimmer.apply(Command::On, at(0)), Some(MAX));
+        assert_eq!(dimmer.apply(Command::Off, at(0)), Some(0));
+        assert_eq!(dimmer.apply(Command::Interval(3), at(0)), None);
+        assert_eq!(dimmer.apply(Command::Heartbeat, at(0)), None);
+        assert!(!dimmer.is_fading());
+    }
+
+    #[test]
+    fn levels() {
+        let mut dimmer = Dimmer::new(CURVE, MAX);
+        assert_eq!(dimmer.apply(Command::Pwm(0), at(0)), Some(0));
+        assert_eq!(dimmer.apply(Command::Pwm(255), at(0)), Some(MAX));
+        // a higher level is never darker
+        let mut last = 0;
+        for level in 0..=u8::MAX {
+            let duty = dimmer.apply(Command::Pwm(level), at(0)).unwrap();
+            assert!(duty >= last);
+            last = duty;
+        }
+        let mut linear = Dimmer::new(Curve::Gamma(10), MAX);
+        assert_eq!(linear.apply(Command::Pwm(128), at(0)), Some(1205));
+    }
+
+    #[test]
+    fn fades_step_by_step() {
+        let mut dimmer = Dimmer::new(Curve::Gamma(10), MAX);
+        assert_eq!(dimmer.apply(fade(255, 1000), at(0)), Some(0));
+        assert!(dimmer.is_fading());
+        assert_eq!(dimmer.step(at(250)), MAX / 4);
+        assert_eq!(dimmer.step(at(500)), MAX / 2);
+        assert!(dimmer.is_fading());
+        assert_eq!(dimmer.step(at(1000)), MAX);
+        assert!(!dimmer.is_fading());
+        // a fade of 0 ms is a jump
+        assert_eq!(dimmer.apply(fade(0, 0), at(1005)), Some(0));
+        assert!(!dimmer.is_fading());
+    }
+
+    #[test]
+    fn retargets_from_where_the_led_is() {
+        let mut dimmer = Dimmer::new(Curve::Gamma(10), MAX);
+        dimmer.apply(fade(255, 1000), at(0));
+        // half way up, back down to 0 in 500 ms
+        assert_eq!(dimmer.apply(fade(0, 500), at(500)), Some(MAX / 2));
+        assert_eq!(dimmer.step(at(750)), MAX / 4);
+        // a jump ends the fade
+        assert_eq!(dimmer.apply(Command::On, at(800)), Some(MAX));
+        assert!(!dimmer.is_fading());
+        assert_eq!(dimmer.step(at(900)), MAX);
+    }
+}
@@ -0,0 +1,151 @@
+//! The steps of a `Command::Fade`, from the current level of the led to its target.
+//!
+//! The levels are the brightness seen, out of `u16::MAX`, and go through the `Curve`
+//! after the easing: a linear fade looks linear. A task steps the duty every
+//! `FADE_STEP` until the fade `is_over`.
+
+use protocol::{Duration, Easing, Instant};
+
+/// How often the duty changes during a fade, 100 Hz is smooth for the eye.
+pub const FADE_STEP: Duration = Duration::millis(10);
+
+/// 1.0 in the fixed point of `ease`.
+const ONE: u64 = u16::MAX as u64;
+
+/// How far the level is, out of `u16::MAX`, when the fade is `progress` out of
+/// `u16::MAX` done. 0 gives 0 and `u16::MAX` gives `u16::MAX`, whatever the easing.
+pub fn ease(easing: Easing, progress: u16) -> u16 {
+    let p = u64::from(progress);
+    let eased = match easing {
+        Easing::Linear => p,
+        Easing::EaseIn => p * p / ONE,
+        Easing::EaseOut => ONE - (ONE - p) * (ONE - p) / ONE,
+        // smoothstep, 3p² - 2p³
+        Easing::EaseInOut => p * p * (3 * ONE - 2 * p) / (ONE * ONE),
+        // (2 ^ 10p - 1) / 1023, 2 ^ frac being 1 + frac between the powers of 2
+        Easing::Exponential => {
+            let x = 10 * p;
+            let power = (1 << (x / ONE)) * (ONE + x % ONE);
+            (power - ONE) / 1023
+        }
+    };
+    eased as u16
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fade {
+    from: u16,
+    to: u16,
+    start: Instant,
+    duration: Duration,
+    easing: Easing,
+}
+
+impl Fade {
+    /// From the level `from` at `start` to the level `to`, `duration` later.
+    pub fn new(from: u16, to: u16, start: Instant, duration: Duration, easing: Easing) -> Self {
+        Fade {
+            from,
+            to,
+            start,
+            duration,
+            easing,
+        }
+    }
+
+    /// The level at `now`, `to` once the fade is over.
+    pub fn level(&self, now: Instant) -> u16 {
+        let elapsed = now
+            .checked_duration_since(self.start)
+            .unwrap_or(Duration::from_ticks(0));
+        if elapsed >= self.duration {
+            return self.to;
+        }
+        let duration = u64::from(self.duration.ticks());
+        let progress = (u64::from(elapsed.ticks()) * ONE + duration / 2) / duration;
+        let eased = i64::from(ease(self.easing, progress as u16));
+        let (from, to) = (i64::from(self.from), i64::from(self.to));
+        (from + (to - from) * eased / ONE as i64) as u16
+    }
+
+    pub fn is_over(&self, now: Instant) -> bool {
+        now.checked_duration_since(self.start)
+            .is_some_and(|elapsed| elapsed >= self.duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::Exponential,
+    ];
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    #[test]
+    fn every_easing_starts_and_ends_in_place() {
+        for easing in EASINGS {
+            assert_eq!(ease(easing, 0), 0, "{:?}", easing);
+            assert_eq!(ease(easing, u16::MAX), u16::MAX, "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn every_easing_only_goes_forward() {
+        for easing in EASINGS {
+            let mut last = 0;
+            for progress in 0..=u16::MAX {
+                let eased = ease(easing, progress);
+                assert!(eased >= last, "{:?} at {}", easing, progress);
+                last = eased;
+            }
+        }
+    }
+
+    #[test]
+    fn halfway() {
+        let half = u16::MAX / 2;
+        assert_eq!(ease(Easing::Linear, half), half);
+        assert!(ease(Easing::EaseIn, half) < half / 2 + 1);
+        assert!(ease(Easing::EaseOut, half) > half + half / 2);
+        assert!(ease(Easing::EaseInOut, half).abs_diff(half) <= 1);
+        // 2 ^ 5 / 2 ^ 10, the exponential is still nearly dark
+        assert!(ease(Easing::Exponential, half) < u16::MAX / 32);
+    }
+
+    #[test]
+    fn fades_down_too() {
+        let fade = Fade::new(60_000, 0, at(100), Duration::millis(1000), Easing::Linear);
+        assert_eq!(fade.level(at(0)), 60_000);
+        assert_eq!(fade.level(at(100)), 60_000);
+        assert_eq!(fade.level(at(600)), 30_000);
+        assert!(!fade.is_over(at(1099)));
+        assert!(fade.is_over(at(1100)));
+        assert_eq!(fade.level(at(1100)), 0);
+        assert_eq!(fade.level(at(5000)), 0);
+    }
+
+    #[test]
+    fn no_duration_is_a_jump() {
+        let fade = Fade::new(0, 1000, at(5), Duration::millis(0), Easing::EaseIn);
+        assert!(fade.is_over(at(5)));
+        assert_eq!(fade.level(at(5)), 1000);
+    }
+
+    #[test]
+    fn survives_the_wrap_of_the_timer() {
+        let start = Instant::from_ticks(u32::MAX - 499_999);
+        let fade = Fade::new(0, 10_000, start, Duration::millis(1000), Easing::Linear);
+        assert_eq!(fade.level(at(0)), 5_000);
+        assert!(!fade.is_over(at(0)));
+        assert!(fade.is_over(at(500)));
+    }
+}
@@ -0,0 +1,323 @@
+//! Presses, releases, long presses, double clicks and auto-repeat of a button, from its
+//! edges and its level. The same for the GPIOTE of the nRF52 and the EXTI of the Nucleo.
+//!
+//! The edge interrupt calls `Button::on_edge`, and a task `poll`s the level of the pin
+//! at the `deadline`. A bounce is an edge too: a level is only believed when no edge
+//! came for `Timings::debounce`. The interrupt can listen to presses only: while the
+//! button is held, the deadline comes every `debounce` to see the release.
+
+use protocol::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Gesture {
+    Press,
+    Release,
+    /// Held for `Timings::long_press`.
+    LongPress,
+    /// A press shortly after a click, see `Timings::double_click`. It comes instead of
+    /// the `Press`.
+    DoubleClick,
+    /// Still held, every `Timings::repeat` after the `LongPress`.
+    Repeat,
+}
+
+impl Gesture {
+    /// A press, the second one of a double click too.
+    pub fn is_press(self) -> bool {
+        matches!(self, Gesture::Press | Gesture::DoubleClick)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timings {
+    /// How long a level must last without an edge to be believed.
+    pub debounce: Duration,
+    pub long_press: Duration,
+    /// The longest time between the release of a click and the next press.
+    pub double_click: Duration,
+    /// None for no auto-repeat.
+    pub repeat: Option<Duration>,
+}
+
+impl Timings {
+    pub const DEFAULT: Timings = Timings {
+        debounce: Duration::millis(10),
+        long_press: Duration::millis(600),
+        double_click: Duration::millis(300),
+        repeat: None,
+    };
+
+    pub const fn with_repeat(self, every: Duration) -> Self {
+        Timings {
+            repeat: Some(every),
+            ..self
+        }
+    }
+}
+
+pub struct Button {
+    timings: Timings,
+    pressed: bool,
+    /// The last edge, until the level after it is believed.
+    edge: Option<Instant>,
+    polled: Option<Instant>,
+    pressed_at: Option<Instant>,
+    long: bool,
+    double: bool,
+    next_repeat: Option<Instant>,
+    /// The release of the last click, for a double click.
+    clicked: Option<Instant>,
+}
+
+impl Button {
+    pub const fn new(timings: Timings) -> Self {
+        Button {
+            timings,
+            pressed: false,
+            edge: None,
+            polled: None,
+            pressed_at: None,
+            long: false,
+            double: false,
+            next_repeat: None,
+            clicked: None,
+        }
+    }
+
+    /// The pin changed at `now`, or bounced. When to poll it, unless a poll is already
+    /// scheduled.
+    pub fn on_edge(&mut self, now: Instant) -> Option<Instant> {
+        let scheduled = self.deadline().is_some();
+        self.edge = Some(now);
+        if scheduled {
+            None
+        } else {
+            self.deadline()
+        }
+    }
+
+    /// Reads the level of the pin, `pressed` at `now`. Polling before the deadline
+    /// does no harm.
+    pub fn poll(&mut self, pressed: bool, now: Instant) -> Option<Gesture> {
+        self.polled = Some(now);
+        if let Some(edge) = self.edge {
+            if since(edge, now) < self.timings.debounce {
+                return None;
+            }
+            self.edge = None;
+        }
+
+        if pressed != self.pressed {
+            self.pressed = pressed;
+            return Some(if pressed {
+                self.press(now)
+            } else {
+                self.release(now)
+            });
+        }
+        if !pressed {
+            return None;
+        }
+        if !self.long {
+            if since(self.pressed_at?, now) < self.timings.long_press {
+                return None;
+            }
+            self.long = true;
+            self.next_repeat = self.timings.repeat.map(|every| now + every);
+            return Some(Gesture::LongPress);
+        }
+        let (every, at) = (self.timings.repeat?, self.next_repeat?);
+        now.checked_duration_since(at)?;
+        self.next_repeat = Some(at + every);
+        Some(Gesture::Repeat)
+    }
+
+    fn press(&mut self, now: Instant) -> Gesture {
+        self.pressed_at = Some(now);
+        self.long = false;
+        self.next_repeat = None;
+        self.double = self
+            .clicked
+            .take()
+            .is_some_and(|at| since(at, now) < self.timings.double_click);
+        if self.double {
+            Gesture::DoubleClick
+        } else {
+            Gesture::Press
+        }
+    }
+
+    fn release(&mut self, now: Instant) -> Gesture {
+        // Neither a long press nor the end of a double click starts a double click
+        if !self.long && !self.double {
+            self.clicked = Some(now);
+        }
+        Gesture::Release
+    }
+
+    /// When to `poll` next, None when the button is released and settled.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.edge, self.polled) {
+            (Some(edge), _) => Some(edge + self.timings.debounce),
+            (None, Some(polled)) if self.pressed => Some(polled + self.timings.debounce),
+            _ => None,
+        }
+    }
+
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
+
+/// The first deadline of several buttons polled by the same task.
+pub fn next_deadline(buttons: &[Button]) -> Option<Instant> {
+    buttons.iter().filter_map(Button::deadline).min()
+}
+
+// The timers of both boards wrap after ~71 minutes
+fn since(at: Instant, now: Instant) -> Duration {
+    now.checked_duration_since(at)
+        .unwrap_or(Duration::from_ticks(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    /// Runs a trace of the levels of the pin, `(ms, pressed)`, polling at every
+    /// deadline. `presses_only` for the interrupts that only see falling edges.
+    fn run(timings: Timings, trace: &[(u32, bool)], presses_only: bool) -> Vec<(u32, Gesture)> {
+        let mut button = Button::new(timings);
+        let mut level = false;
+        let mut edges = trace.iter().peekable();
+        let mut gestures = Vec::new();
+        loop {
+            let edge = edges.peek().map(|&&(ms, _)| at(ms));
+            let now = match (edge, button.deadline()) {
+                (Some(edge), Some(deadline)) => edge.min(deadline),
+                (Some(edge), None) => edge,
+                (None, Some(deadline)) => deadline,
+                (None, None) => return gestures,
+            };
+            if edge == Some(now) {
+                level = edges.next().unwrap().1;
+                if level || !presses_only {
+                    button.on_edge(now);
+                }
+            } else if let Some(gesture) = button.poll(level, now) {
+                gestures.push((now.ticks() / 1000, gesture));
+            }
+        }
+    }
+
+    #[test]
+    fn bounces_are_ignored() {
+        let trace = [
+            (100, true),
+            (101, false),
+            (103, true),
+            (104, false),
+            (106, true),
+            (300, false),
+            (301, true),
+            (302, false),
+        ];
+        let expected = [(116, Gesture::Press), (312, Gesture::Release)];
+        assert_eq!(run(Timings::DEFAULT, &trace, false), expected);
+        // Only the presses interrupt, the release is seen by the polls while held
+        assert_eq!(
+            run(Timings::DEFAULT, &trace, true),
+            [(116, Gesture::Press), (311, Gesture::Release)]
+        );
+    }
+
+    #[test]
+    fn long_press_and_auto_repeat() {
+        let timings = Timings {
+            long_press: Duration::millis(500),
+            ..Timings::DEFAULT
+        }
+        .with_repeat(Duration::millis(100));
+        assert_eq!(
+            run(timings, &[(0, true), (1000, false)], false),
+            [
+                (10, Gesture::Press),
+                (510, Gesture::LongPress),
+                (610, Gesture::Repeat),
+                (710, Gesture::Repeat),
+                (810, Gesture::Repeat),
+                (910, Gesture::Repeat),
+                (1010, Gesture::Release)
+            ]
+        );
+        // Without auto-repeat, a long press is a long press
+        assert_eq!(
+            run(Timings::DEFAULT, &[(0, true), (1000, false)], false),
+            [
+                (10, Gesture::Press),
+                (610, Gesture::LongPress),
+                (1010, Gesture::Release)
+            ]
+        );
+    }
+
+    #[test]
+    fn double_click() {
+        let trace = [
+            (0, true),
+            (50, false),
+            (150, true),
+            (200, false),
+            // a third press is a new click
+            (300, true),
+            (350, false),
+            // too late for a double click
+            (1000, true),
+            (1050, false),
+        ];
+        assert_eq!(
+            run(Timings::DEFAULT, &trace, false),
+            [
+                (10, Gesture::Press),
+                (60, Gesture::Release),
+                (160, Gesture::DoubleClick),
+                (210, Gesture::Release),
+                (310, Gesture::Press),
+                (360, Gesture::Release),
+                (1010, Gesture::Press),
+                (1060, Gesture::Release)
+            ]
+        );
+    }
+
+    #[test]
+    fn one_poll_scheduled_at_a_time() {
+        let mut button = Button::new(Timings::DEFAULT);
+        assert_eq!(button.on_edge(at(0)), Some(at(10)));
+        // the bounces move the deadline, the poll scheduled at 10 ms reschedules
+        assert_eq!(button.on_edge(at(4)), None);
+        assert_eq!(button.poll(true, at(10)), None);
+        assert_eq!(button.deadline(), Some(at(14)));
+        assert_eq!(button.poll(true, at(14)), Some(Gesture::Press));
+        assert!(button.is_pressed());
+
+        let other = Button::new(Timings::DEFAULT);
+        assert_eq!(next_deadline(&[other, button]), Some(at(24)));
+    }
+
+    #[test]
+    fn survives_the_wrap_of_the_timer() {
+        let mut button = Button::new(Timings::DEFAULT);
+        let before = Instant::from_ticks(u32::MAX - 4_999);
+        let poll = button.on_edge(before).unwrap();
+        assert_eq!(poll, at(5));
+        assert_eq!(button.poll(true, at(4)), None);
+        assert_eq!(button.poll(true, poll), Some(Gesture::Press));
+    }
+}
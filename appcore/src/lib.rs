@@ -0,0 +1,20 @@
+//! The logic of the examples, as plain state machines.
+//!
+//! The RTIC tasks only move data between the peripherals and these state machines,
+//! so the behaviour can be tested on the computer, and simulated by the `host` tools.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod blinker;
+pub mod board;
+pub mod brightness;
+pub mod channels;
+pub mod dimmer;
+pub mod fade;
+pub mod gesture;
+pub mod pattern;
+pub mod remote;
+pub mod ring;
+pub mod sleep;
+pub mod transport;
+pub mod txqueue;
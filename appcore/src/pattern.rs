@@ -0,0 +1,417 @@
+//! The Nucleo side of the patterns of `interval_08`: breathing, heartbeat, strobe,
+//! Morse code, or the steps uploaded by the nRF52, instead of the blink.
+//!
+//! A pattern is a list of `Step`s, a level held for a while, played a number of times.
+//! A task shows the `Beat` of `Sequencer::beat` and spawns itself again after it.
+
+use protocol::{Command, Duration, Pattern};
+
+use crate::brightness::Curve;
+
+/// Most steps of a pattern, a Morse text of `MORSE_LEN` digits included.
+pub const MAX_STEPS: usize = 128;
+/// The length of a dot, a dash is 3 of them.
+pub const MORSE_UNIT: Duration = Duration::millis(150);
+
+/// The levels of `Pattern::Breathing` from dark to bright, then back.
+const BREATHING_STEPS: u8 = 32;
+const BREATHING_STEP: Duration = Duration::millis(50);
+
+/// A level, the brightness seen out of 255, held for `duration`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub level: u8,
+    pub duration: Duration,
+}
+
+const fn step(level: u8, ms: u32) -> Step {
+    Step {
+        level,
+        duration: Duration::millis(ms),
+    }
+}
+
+const HEARTBEAT: [Step; 4] = [step(255, 100), step(0, 100), step(160, 100), step(0, 700)];
+const STROBE: [Step; 2] = [step(255, 20), step(0, 80)];
+
+/// What the pattern task must do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Beat {
+    /// Duty to set on the pwm channel.
+    pub duty: u16,
+    /// When to show the next one.
+    pub next_in: Duration,
+}
+
+/// The steps of a pattern.
+#[derive(Clone, Copy)]
+pub struct Steps {
+    steps: [Step; MAX_STEPS],
+    len: usize,
+}
+
+impl Steps {
+    pub const fn new() -> Self {
+        Steps {
+            steps: [step(0, 0); MAX_STEPS],
+            len: 0,
+        }
+    }
+
+    fn from_slice(steps: &[Step]) -> Self {
+        let mut all = Steps::new();
+        for &step in steps {
+            all.push(step);
+        }
+        all
+    }
+
+    /// False when there is no room left.
+    fn push(&mut self, step: Step) -> bool {
+        match self.steps.get_mut(self.len) {
+            Some(free) => {
+                *free = step;
+                self.len += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Makes the darkness at the end last at least `units` of Morse.
+    fn pause(&mut self, units: u32) {
+        let duration = MORSE_UNIT * units;
+        match self.as_slice().last() {
+            Some(last) if last.level == 0 => {
+                self.steps[self.len - 1].duration = last.duration.max(duration)
+            }
+            _ => {
+                self.push(Step { level: 0, duration });
+            }
+        }
+    }
+
+    pub fn as_slice(&self) -> &[Step] {
+        &self.steps[..self.len]
+    }
+}
+
+impl Default for Steps {
+    fn default() -> Self {
+        Steps::new()
+    }
+}
+
+/// The steps of a built-in pattern.
+pub fn builtin(pattern: Pattern) -> Steps {
+    match pattern {
+        Pattern::Breathing => {
+            let mut steps = Steps::new();
+            let level = |i: u8| (u16::from(i) * 255 / u16::from(BREATHING_STEPS)) as u8;
+            for i in (0..BREATHING_STEPS).chain((1..=BREATHING_STEPS).rev()) {
+                steps.push(Step {
+                    level: level(i),
+                    duration: BREATHING_STEP,
+                });
+            }
+            steps
+        }
+        Pattern::Heartbeat => Steps::from_slice(&HEARTBEAT),
+        Pattern::Strobe => Steps::from_slice(&STROBE),
+        // Nothing built in
+        Pattern::Uploaded => Steps::new(),
+    }
+}
+
+/// Dots and dashes of the letters, then of the digits.
+const MORSE: [&str; 36] = [
+    ".-", "-...", "-.-.", "-..", ".", "..-.", "--.", "....", "..", ".---", "-.-", ".-..", "--",
+    "-.", "---", ".--.", "--.-", ".-.", "...", "-", "..-", "...-", ".--", "-..-", "-.--", "--..",
+    "-----", ".----", "..---", "...--", "....-", ".....", "-....", "--...", "---..", "----.",
+];
+
+fn morse_code(c: u8) -> Option<&'static str> {
+    match c.to_ascii_uppercase() {
+        c @ b'A'..=b'Z' => Some(MORSE[usize::from(c - b'A')]),
+        c @ b'0'..=b'9' => Some(MORSE[usize::from(c - b'0') + 26]),
+        _ => None,
+    }
+}
+
+/// The steps of `text` in Morse code, until its first 0. A dot is lit one
+/// `MORSE_UNIT`, a dash 3, with 1 unit between them, 3 between the letters and 7
+/// between the words, and after the text. What is not a letter or a digit is a space.
+pub fn morse(text: &[u8]) -> Steps {
+    let mut steps = Steps::new();
+    for &c in text.iter().take_while(|&&c| c != 0) {
+        match morse_code(c) {
+            Some(code) => {
+                for symbol in code.bytes() {
+                    let units = if symbol == b'-' { 3 } else { 1 };
+                    steps.push(Step {
+                        level: u8::MAX,
+                        duration: MORSE_UNIT * units,
+                    });
+                    steps.pause(1);
+                }
+                steps.pause(3);
+            }
+            None => steps.pause(7),
+        }
+    }
+    if !steps.as_slice().is_empty() {
+        steps.pause(7);
+    }
+    steps
+}
+
+/// Plays the patterns on a pwm channel of `max_duty`.
+pub struct Sequencer {
+    curve: Curve,
+    max_duty: u16,
+    uploaded: Steps,
+    playing: Steps,
+    next: usize,
+    /// The rounds left after this one, None for ever.
+    rounds_left: Option<u8>,
+    running: bool,
+}
+
+impl Sequencer {
+    pub const fn new(max_duty: u16) -> Self {
+        Sequencer {
+            curve: Curve::DEFAULT,
+            max_duty,
+            uploaded: Steps::new(),
+            playing: Steps::new(),
+            next: 0,
+            rounds_left: None,
+            running: false,
+        }
+    }
+
+    /// How the levels turn into a duty, `Curve::DEFAULT` otherwise.
+    pub const fn with_curve(self, curve: Curve) -> Self {
+        Sequencer { curve, ..self }
+    }
+
+    /// Handles `command`. True when the led must follow `beat` right away: a pattern
+    /// starts, or it stopped and the led blinks again.
+    pub fn apply(&mut self, command: Command) -> bool {
+        match command {
+            Command::Play { pattern, repeat } => {
+                let steps = match pattern {
+                    Pattern::Uploaded => self.uploaded,
+                    _ => builtin(pattern),
+                };
+                self.start(steps, repeat)
+            }
+            Command::Morse { text, repeat } => self.start(morse(&text), repeat),
+            Command::Step {
+                index,
+                level,
+                duration_ms,
+            } => {
+                let index = usize::from(index);
+                if index <= self.uploaded.len {
+                    self.uploaded.len = index;
+                    // a pattern of 0 ms steps would never let go of the cpu
+                    let duration = Duration::millis(duration_ms.max(1).into());
+                    self.uploaded.push(Step { level, duration });
+                }
+                false
+            }
+            Command::Stop => self.stop(),
+            _ => false,
+        }
+    }
+
+    fn start(&mut self, steps: Steps, repeat: u8) -> bool {
+        self.playing = steps;
+        self.next = 0;
+        self.rounds_left = repeat.checked_sub(1);
+        self.running = !steps.as_slice().is_empty();
+        true
+    }
+
+    /// Stops the pattern, true if one was playing.
+    pub fn stop(&mut self) -> bool {
+        core::mem::replace(&mut self.running, false)
+    }
+
+    /// The next step to show, None once the pattern is over.
+    pub fn beat(&mut self) -> Option<Beat> {
+        if !self.running {
+            return None;
+        }
+        if self.next == self.playing.len {
+            match self.rounds_left {
+                Some(0) => {
+                    self.running = false;
+                    return None;
+                }
+                Some(rounds) => self.rounds_left = Some(rounds - 1),
+                None => {}
+            }
+            self.next = 0;
+        }
+        let step = self.playing.steps[self.next];
+        self.next += 1;
+        Some(Beat {
+            duty: self.curve.duty8(step.level, self.max_duty),
+            next_in: step.duration,
+        })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.running
+    }
+
+    pub fn uploaded(&self) -> &[Step] {
+        self.uploaded.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::MORSE_LEN;
+    use std::vec::Vec;
+
+    const MAX: u16 = 255;
+
+    /// The levels and durations in ms of a whole play.
+    fn play(sequencer: &mut Sequencer) -> Vec<(u16, u32)> {
+        core::iter::from_fn(|| sequencer.beat())
+            .map(|beat| (beat.duty, beat.next_in.to_millis()))
+            .collect()
+    }
+
+    fn linear() -> Sequencer {
+        Sequencer::new(MAX).with_curve(Curve::Gamma(10))
+    }
+
+    #[test]
+    fn plays_a_builtin_the_times_asked() {
+        let mut sequencer = linear();
+        assert!(!sequencer.is_playing());
+        assert_eq!(sequencer.beat(), None);
+        assert!(sequencer.apply(Command::Play {
+            pattern: Pattern::Strobe,
+            repeat: 2,
+        }));
+        assert!(sequencer.is_playing());
+        assert_eq!(
+            play(&mut sequencer),
+            [(255, 20), (0, 80), (255, 20), (0, 80)]
+        );
+        assert!(!sequencer.is_playing());
+    }
+
+    #[test]
+    fn forever_until_stopped() {
+        let mut sequencer = linear();
+        sequencer.apply(Command::Play {
+            pattern: Pattern::Heartbeat,
+            repeat: 0,
+        });
+        for _ in 0..1000 {
+            assert!(sequencer.beat().is_some());
+        }
+        assert!(sequencer.apply(Command::Stop));
+        assert_eq!(sequencer.beat(), None);
+        // nothing to stop
+        assert!(!sequencer.stop());
+    }
+
+    #[test]
+    fn breathing_goes_up_and_down() {
+        let steps = builtin(Pattern::Breathing);
+        let levels: Vec<u8> = steps.as_slice().iter().map(|s| s.level).collect();
+        let top = levels.iter().position(|&l| l == 255).unwrap();
+        assert!(levels[..=top].windows(2).all(|w| w[0] < w[1]));
+        assert!(levels[top..].windows(2).all(|w| w[0] > w[1]));
+        assert_eq!(levels.first(), Some(&0));
+    }
+
+    #[test]
+    fn morse_code() {
+        let ms = |text: &[u8]| -> Vec<(u8, u32)> {
+            morse(text)
+                .as_slice()
+                .iter()
+                .map(|s| (s.level, s.duration.to_millis()))
+                .collect()
+        };
+        // S is 3 dots, O 3 dashes, the gaps merge
+        assert_eq!(
+            ms(b"SO\0garbage"),
+            [
+                (255, 150),
+                (0, 150),
+                (255, 150),
+                (0, 150),
+                (255, 150),
+                (0, 450),
+                (255, 450),
+                (0, 150),
+                (255, 450),
+                (0, 150),
+                (255, 450),
+                (0, 1050),
+            ]
+        );
+        assert_eq!(ms(b"e e"), [(255, 150), (0, 1050), (255, 150), (0, 1050)]);
+        assert_eq!(ms(b""), []);
+        // the longest text, of the longest codes, fits
+        assert_eq!(morse(&[b'0'; MORSE_LEN]).as_slice().len(), 10 * MORSE_LEN);
+    }
+
+    #[test]
+    fn uploaded_steps() {
+        let mut sequencer = linear();
+        let step = |index, level, duration_ms| Command::Step {
+            index,
+            level,
+            duration_ms,
+        };
+        assert!(!sequencer.apply(step(0, 255, 300)));
+        sequencer.apply(step(1, 10, 0));
+        // a step too far is ignored, a step again replaces it and the ones after it
+        sequencer.apply(step(5, 10, 100));
+        sequencer.apply(step(1, 20, 200));
+        sequencer.apply(step(0, 30, 100));
+        sequencer.apply(step(1, 40, 0));
+        assert_eq!(
+            sequencer.uploaded(),
+            [
+                Step {
+                    level: 30,
+                    duration: Duration::millis(100)
+                },
+                Step {
+                    level: 40,
+                    duration: Duration::millis(1)
+                }
+            ]
+        );
+        sequencer.apply(Command::Play {
+            pattern: Pattern::Uploaded,
+            repeat: 1,
+        });
+        assert_eq!(play(&mut sequencer), [(30, 100), (40, 1)]);
+    }
+
+    #[test]
+    fn nothing_to_play() {
+        let mut sequencer = linear();
+        assert!(sequencer.apply(Command::Play {
+            pattern: Pattern::Uploaded,
+            repeat: 0,
+        }));
+        assert!(!sequencer.is_playing());
+        assert!(!sequencer.apply(Command::Pwm(3)));
+        assert!(sequencer.apply(Command::morse("", 0)));
+        assert_eq!(sequencer.beat(), None);
+    }
+}
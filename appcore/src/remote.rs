@@ -0,0 +1,140 @@
+//! The nRF52 side of `postcard_06` to `reliable_09`: buttons that switch the led of
+//! the Nucleo, or change its interval and its brightness.
+
+use protocol::{Command, MAX_BLINK_MS};
+
+/// Step of the brightness for every press, in `interval_08` and `reliable_09`.
+pub const FINE_STEP: u8 = 8;
+/// Step of `pwm_07`, to have 8 levels of brightness.
+pub const COARSE_STEP: u8 = 32;
+/// Step of the blinking interval for every press.
+pub const INTERVAL_STEP_MS: u32 = 250;
+
+/// What `postcard_06` and `pwm_07` send, with the presses of the on and off buttons
+/// recognized by `gesture::Button`. On wins when both come at once, nothing is sent
+/// when none does.
+pub fn switch(on: bool, off: bool) -> Option<Command> {
+    if on {
+        Some(Command::On)
+    } else if off {
+        Some(Command::Off)
+    } else {
+        None
+    }
+}
+
+pub struct Remote {
+    /// As long lit as dark, 0 for steadily on.
+    interval_ms: u32,
+    pwm: u8,
+    step: u8,
+}
+
+impl Remote {
+    pub const fn new(step: u8) -> Self {
+        Remote {
+            interval_ms: 1000,
+            pwm: 0,
+            step,
+        }
+    }
+
+    /// What `read_buttons` sends, with the presses of the interval buttons recognized by
+    /// `gesture::Button`, or its repeats. Down to 0, the led stays on.
+    pub fn interval(&mut self, up: bool, down: bool) -> Command {
+        if up {
+            self.interval_ms = (self.interval_ms + INTERVAL_STEP_MS).min(MAX_BLINK_MS);
+        }
+        if down {
+            self.interval_ms = self.interval_ms.saturating_sub(INTERVAL_STEP_MS);
+        }
+        Command::blink(self.interval_ms, self.interval_ms)
+    }
+
+    /// What `read_buttons` sends, with the presses of the brightness buttons recognized
+    /// by `gesture::Button`, or its repeats. Brighter wins when both come at once.
+    pub fn brightness(&mut self, on: bool, off: bool) -> Command {
+        if on && self.pwm < 255 {
+            self.pwm = self.pwm.saturating_add(self.step);
+        } else if off && self.pwm > 0 {
+            self.pwm = self.pwm.saturating_sub(self.step);
+        }
+        Command::Pwm(self.pwm)
+    }
+
+    pub fn interval_ms(&self) -> u32 {
+        self.interval_ms
+    }
+
+    pub fn pwm(&self) -> u8 {
+        self.pwm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blink(ms: u32) -> Command {
+        Command::Blink {
+            on_ms: ms,
+            off_ms: ms,
+        }
+    }
+
+    #[test]
+    fn interval_buttons() {
+        let mut remote = Remote::new(FINE_STEP);
+        assert_eq!(remote.interval(true, false), blink(1250));
+        assert_eq!(remote.interval(true, true), blink(1250));
+        assert_eq!(remote.interval(false, true), blink(1000));
+        // neither pressed: the interval stays, `read_buttons` does not send it then
+        assert_eq!(remote.interval(false, false), blink(1000));
+        for _ in 0..3 {
+            remote.interval(false, true);
+        }
+        // steadily on, it does not wrap around any more
+        assert_eq!(remote.interval(false, true), blink(0));
+        assert_eq!(remote.interval(false, true), blink(0));
+        assert_eq!(remote.interval(true, false), blink(INTERVAL_STEP_MS));
+        for _ in 0..20_000 {
+            remote.interval(true, false);
+        }
+        assert_eq!(remote.interval(true, false), blink(MAX_BLINK_MS));
+    }
+
+    #[test]
+    fn brightness_buttons() {
+        let mut remote = Remote::new(FINE_STEP);
+        assert_eq!(remote.brightness(false, true), Command::Pwm(0));
+        assert_eq!(remote.brightness(true, false), Command::Pwm(8));
+        assert_eq!(remote.brightness(true, true), Command::Pwm(16));
+        for _ in 0..40 {
+            remote.brightness(true, false);
+        }
+        assert_eq!(remote.pwm(), 255);
+        assert_eq!(remote.brightness(false, true), Command::Pwm(247));
+        assert_eq!(remote.interval_ms(), 1000);
+    }
+
+    #[test]
+    fn coarse_steps() {
+        let mut remote = Remote::new(COARSE_STEP);
+        let levels: [u8; 9] = core::array::from_fn(|_| match remote.brightness(true, false) {
+            Command::Pwm(level) => level,
+            _ => unreachable!(),
+        });
+        assert_eq!(levels, [32, 64, 96, 128, 160, 192, 224, 255, 255]);
+        assert_eq!(remote.brightness(false, true), Command::Pwm(223));
+        assert_eq!(remote.brightness(false, false), Command::Pwm(223));
+    }
+
+    #[test]
+    fn switch_buttons() {
+        assert_eq!(switch(true, false), Some(Command::On));
+        assert_eq!(switch(true, true), Some(Command::On));
+        assert_eq!(switch(false, true), Some(Command::Off));
+        // no press, nothing to send
+        assert_eq!(switch(false, false), None);
+    }
+}
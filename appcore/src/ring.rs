@@ -0,0 +1,70 @@
+//! Where to read next in a buffer a DMA fills in circles.
+//!
+//! The DMA only tells how many bytes it still has to write before it wraps (`NDTR` on
+//! the STM32), so the write position is `N - remaining`. When it has written exactly `N`
+//! bytes since the last read, the ring looks empty: drain it at least every half turn,
+//! which the half and full transfer interrupts do.
+
+pub struct Ring<const N: usize> {
+    read: usize,
+}
+
+impl<const N: usize> Ring<N> {
+    pub const fn new() -> Self {
+        Ring { read: 0 }
+    }
+
+    /// The index of the next byte to read, if the DMA wrote it already.
+    pub fn next(&mut self, remaining: u16) -> Option<usize> {
+        if self.pending(remaining) == 0 {
+            return None;
+        }
+        let at = self.read;
+        self.read = (self.read + 1) % N;
+        Some(at)
+    }
+
+    /// How many bytes were written and not read yet.
+    pub fn pending(&self, remaining: u16) -> usize {
+        let write = (N - remaining as usize) % N;
+        (write + N - self.read) % N
+    }
+}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_dma() {
+        let mut ring = Ring::<8>::new();
+        // nothing written yet, `remaining` is the whole buffer
+        assert_eq!(ring.next(8), None);
+        assert_eq!(ring.pending(5), 3);
+        assert_eq!(ring.next(5), Some(0));
+        assert_eq!(ring.next(5), Some(1));
+        assert_eq!(ring.next(5), Some(2));
+        assert_eq!(ring.next(5), None);
+    }
+
+    #[test]
+    fn wraps() {
+        let mut ring = Ring::<8>::new();
+        while ring.next(2).is_some() {}
+        // 4 more bytes: 6 and 7, then 0 and 1 again
+        let read: Vec<usize> = core::iter::from_fn(|| ring.next(6)).collect();
+        assert_eq!(read, [6, 7, 0, 1]);
+        // the DMA reloaded `remaining` at the end of the buffer
+        let mut ring = Ring::<8>::new();
+        while ring.next(1).is_some() {}
+        assert_eq!(ring.pending(0), 1);
+        assert_eq!(ring.next(0), Some(7));
+        assert_eq!(ring.next(8), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! How much of the time the board sleeps, to see what a change costs in battery.
+//!
+//! `idle` timestamps each WFI before and after, and hands both to `SleepMeter::slept`.
+//! Every `period`, the meter tells the fraction of it spent asleep.
+
+use protocol::{Duration, Instant};
+
+/// The time asleep over the last period, in tenths of a percent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SleepReport {
+    pub permille: u16,
+    /// How many times the board woke up.
+    pub wakeups: u32,
+}
+
+pub struct SleepMeter {
+    period: Duration,
+    start: Option<Instant>,
+    asleep: u32,
+    wakeups: u32,
+}
+
+impl SleepMeter {
+    pub const fn new(period: Duration) -> Self {
+        SleepMeter {
+            period,
+            start: None,
+            asleep: 0,
+            wakeups: 0,
+        }
+    }
+
+    /// The board slept from `from` to `to`. Once a period is over, what it slept during it.
+    pub fn slept(&mut self, from: Instant, to: Instant) -> Option<SleepReport> {
+        let start = *self.start.get_or_insert(from);
+        // The timers of both boards wrap after ~71 minutes
+        if let Some(asleep) = to.checked_duration_since(from) {
+            self.asleep = self.asleep.saturating_add(asleep.ticks());
+        }
+        self.wakeups = self.wakeups.wrapping_add(1);
+
+        let elapsed = to.checked_duration_since(start)?;
+        if elapsed < self.period {
+            return None;
+        }
+        let permille = (self.asleep as u64 * 1000 / elapsed.ticks().max(1) as u64).min(1000);
+        let report = SleepReport {
+            permille: permille as u16,
+            wakeups: self.wakeups,
+        };
+        self.start = Some(to);
+        self.asleep = 0;
+        self.wakeups = 0;
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    #[test]
+    fn fraction_asleep() {
+        let mut meter = SleepMeter::new(Duration::millis(100));
+        // awake 1 ms out of every 10, then a whole 10 ms asleep
+        for i in 0..9 {
+            assert_eq!(meter.slept(at(i * 10), at(i * 10 + 9)), None);
+        }
+        assert_eq!(
+            meter.slept(at(90), at(100)),
+            Some(SleepReport {
+                permille: 910,
+                wakeups: 10
+            })
+        );
+        // and again for the next period
+        assert_eq!(meter.slept(at(100), at(150)), None);
+        assert_eq!(meter.slept(at(150), at(200)).unwrap().permille, 1000);
+    }
+
+    #[test]
+    fn survives_the_wrap_of_the_timer() {
+        let mut meter = SleepMeter::new(Duration::millis(10));
+        let before = Instant::from_ticks(u32::MAX - 4_999);
+        let after = Instant::from_ticks(5_000);
+        let report = meter.slept(before, after).unwrap();
+        assert_eq!(report.permille, 1000);
+    }
+}
@@ -0,0 +1,255 @@
+//! Whole frames in and out, whatever carries the bytes: a UART of either board, a
+//! pty or a loopback in the `host` tests.
+
+use core::marker::PhantomData;
+
+use embedded_hal::serial;
+use protocol::{
+    accumulator::{Accumulator, Feed},
+    frame::Stats,
+    Duration, Instant,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::board::write_frame;
+
+pub trait FrameTransport {
+    type Error;
+
+    /// Sends `msg` as one frame, waiting until every byte is out.
+    fn send<T: Serialize>(&mut self, msg: &T) -> Result<(), Self::Error>;
+
+    /// Reads the bytes that arrived until a frame is complete, without waiting for more.
+    /// `now` timestamps them, to drop a partial frame after a silence.
+    fn receive<T: DeserializeOwned>(&mut self, now: Instant) -> nb::Result<T, Self::Error>;
+}
+
+/// A receiver that is read in batches, some time after the bytes came: the DMAs of both
+/// boards. The interrupt that says a batch is there is not when its bytes arrived.
+pub trait Backlog {
+    /// The bytes received and not read yet.
+    fn backlog(&self) -> usize;
+}
+
+/// How to timestamp the byte just read, see `SerialTransport::backdated`.
+struct Backdate<Rx> {
+    backlog: fn(&Rx) -> usize,
+    char_time: Duration,
+}
+
+/// Why nothing was received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    Serial(E),
+    /// A frame was dropped, see `protocol::accumulator::Feed`.
+    Overflow,
+    Decode(protocol::Error),
+    Timeout,
+}
+
+/// Frames over an embedded-hal serial port: the UARTs of both HALs, or the mock.
+pub struct SerialTransport<Tx, Rx> {
+    tx: Tx,
+    rx: Rx,
+    frames: Accumulator,
+    backdate: Option<Backdate<Rx>>,
+}
+
+impl<Tx, Rx> SerialTransport<Tx, Rx> {
+    pub const fn new(tx: Tx, rx: Rx) -> Self {
+        SerialTransport {
+            tx,
+            rx,
+            frames: Accumulator::new(),
+            backdate: None,
+        }
+    }
+
+    /// Drops a partial frame when the line is silent for `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.frames = Accumulator::new().with_timeout(timeout);
+        self
+    }
+
+    /// The sending half, to drain a `TxQueue` in an interrupt.
+    pub fn tx(&mut self) -> &mut Tx {
+        &mut self.tx
+    }
+
+    /// The receiving half, for what the HAL does besides reading, like clearing flags.
+    pub fn rx(&mut self) -> &mut Rx {
+        &mut self.rx
+    }
+
+    /// What was received and dropped so far.
+    pub fn stats(&self) -> &Stats {
+        &self.frames.stats
+    }
+}
+
+impl<Tx, Rx: Backlog> SerialTransport<Tx, Rx> {
+    /// Timestamps every byte as if the batch had come back to back until `now`, one
+    /// `char_time` per byte still waiting after it. Otherwise the part of a frame read
+    /// at a half transfer and the rest read when the line goes idle look a whole batch
+    /// apart, and `with_timeout` drops the frame.
+    pub fn backdated(mut self, char_time: Duration) -> Self {
+        self.backdate = Some(Backdate {
+            backlog: Rx::backlog,
+            char_time,
+        });
+        self
+    }
+}
+
+impl<Tx: serial::Write<u8>> SerialTransport<Tx, Unconnected<Tx::Error>> {
+    /// A transport that only sends, like the nRF52 of `interval_08`.
+    pub const fn sender(tx: Tx) -> Self {
+        SerialTransport::new(tx, Unconnected(PhantomData))
+    }
+}
+
+impl<Rx: serial::Read<u8>> SerialTransport<Unconnected<Rx::Error>, Rx> {
+    /// A transport that only receives, like the Nucleo of `interval_08`.
+    pub const fn receiver(rx: Rx) -> Self {
+        SerialTransport::new(Unconnected(PhantomData), rx)
+    }
+}
+
+impl<E, Tx, Rx> FrameTransport for SerialTransport<Tx, Rx>
+where
+    Tx: serial::Write<u8, Error = E>,
+    Rx: serial::Read<u8, Error = E>,
+{
+    type Error = Error<E>;
+
+    fn send<T: Serialize>(&mut self, msg: &T) -> Result<(), Error<E>> {
+        write_frame(&mut self.tx, msg).map_err(Error::Serial)
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self, now: Instant) -> nb::Result<T, Error<E>> {
+        loop {
+            let byte = self.rx.read().map_err(|e| e.map(Error::Serial))?;
+            let at = match &self.backdate {
+                Some(backdate) => now - backdate.char_time * (backdate.backlog)(&self.rx) as u32,
+                None => now,
+            };
+            match self.frames.feed_at(byte, at) {
+                Feed::Consumed => {}
+                Feed::Frame(msg) => return Ok(msg),
+                Feed::Overflow => return Err(nb::Error::Other(Error::Overflow)),
+                Feed::DecodeError(e) => return Err(nb::Error::Other(Error::Decode(e))),
+                Feed::Timeout => return Err(nb::Error::Other(Error::Timeout)),
+            }
+        }
+    }
+}
+
+/// The missing half of a one way transport: nothing comes in, what goes out is lost.
+pub struct Unconnected<E>(PhantomData<E>);
+
+impl<E> serial::Read<u8> for Unconnected<E> {
+    type Error = E;
+
+    fn read(&mut self) -> nb::Result<u8, E> {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+impl<E> serial::Write<u8> for Unconnected<E> {
+    type Error = E;
+
+    fn write(&mut self, _word: u8) -> nb::Result<(), E> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), E> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::Infallible;
+    use protocol::{frame, Command};
+
+    /// What the other board put on the line so far.
+    struct Line(std::collections::VecDeque<u8>);
+
+    impl serial::Read<u8> for Line {
+        type Error = Infallible;
+
+        fn read(&mut self) -> nb::Result<u8, Infallible> {
+            self.0.pop_front().ok_or(nb::Error::WouldBlock)
+        }
+    }
+
+    impl Backlog for Line {
+        fn backlog(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    fn encoded(command: &Command) -> std::vec::Vec<u8> {
+        let mut buf = [0; protocol::MAX_FRAME_LEN];
+        frame::encode(command, &mut buf).unwrap().to_vec()
+    }
+
+    #[test]
+    fn frames_come_whole() {
+        let bytes = encoded(&Command::Pwm(3));
+        let (first, rest) = bytes.split_at(2);
+        let mut link = SerialTransport::receiver(Line(first.iter().copied().collect()));
+        assert_eq!(link.receive::<Command>(at(0)), Err(nb::Error::WouldBlock));
+        link.rx.0.extend(rest);
+        link.rx.0.extend(encoded(&Command::On));
+        assert_eq!(link.receive(at(1)), Ok(Command::Pwm(3)));
+        assert_eq!(link.receive(at(1)), Ok(Command::On));
+        assert_eq!(link.receive::<Command>(at(1)), Err(nb::Error::WouldBlock));
+        assert_eq!(link.stats().received, 2);
+    }
+
+    #[test]
+    fn a_silence_drops_the_partial_frame() {
+        let bytes = encoded(&Command::Interval(2));
+        let mut link = SerialTransport::receiver(Line(bytes[..2].iter().copied().collect()))
+            .with_timeout(Duration::millis(5));
+        assert_eq!(link.receive::<Command>(at(0)), Err(nb::Error::WouldBlock));
+        link.rx.0.extend(&bytes);
+        assert_eq!(
+            link.receive::<Command>(at(10)),
+            Err(nb::Error::Other(Error::Timeout))
+        );
+        assert_eq!(link.receive(at(10)), Ok(Command::Interval(2)));
+        assert_eq!(link.stats().timeouts, 1);
+    }
+
+    #[test]
+    fn a_batch_read_late_is_not_a_silence() {
+        // at 9600 bauds, read at a half transfer and then when the line went idle
+        let char_time = protocol::accumulator::char_time(9600);
+        let bytes = encoded(&Command::blink(100, 3_600_000));
+        let (first, rest) = bytes.split_at(3);
+        let late = |link: &mut SerialTransport<_, Line>| {
+            link.rx.0.extend(first);
+            assert_eq!(link.receive::<Command>(at(0)), Err(nb::Error::WouldBlock));
+            link.rx.0.extend(rest);
+            // the rest came back to back, the idle line took one more character
+            let idle = Instant::from_ticks(0) + char_time * (rest.len() as u32 + 1);
+            link.receive::<Command>(idle)
+        };
+        let timeout = protocol::accumulator::inter_byte_timeout(9600, 10);
+        let mut link = SerialTransport::receiver(Line(Default::default()))
+            .with_timeout(timeout)
+            .backdated(char_time);
+        assert_eq!(late(&mut link), Ok(Command::blink(100, 3_600_000)));
+        // timestamped when read, the frame looked stale
+        let mut link = SerialTransport::receiver(Line(Default::default())).with_timeout(timeout);
+        assert_eq!(late(&mut link), Err(nb::Error::Other(Error::Timeout)));
+    }
+}
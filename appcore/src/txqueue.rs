@@ -0,0 +1,291 @@
+//! Frames waiting for the UART, so that sending never waits for the bytes to go out.
+//!
+//! The tasks `push` messages; the UART interrupt takes them out when it is ready,
+//! a byte at a time (`next_byte`, the TXE interrupt of the STM32) or a frame at a time
+//! (`next_frame`, the ENDTX event of EasyDMA on the nRF52).
+
+use core::mem::discriminant;
+
+use protocol::{frame, Command, MAX_FRAME_LEN};
+use serde::Serialize;
+
+/// What to do with a message pushed on a full queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Overflow {
+    /// The oldest waiting message makes room.
+    DropOldest,
+    /// The new message is lost.
+    DropNewest,
+    /// The new message replaces a waiting one of the same kind, `Pwm(3)` replaces
+    /// `Pwm(1)`: only the newest matters. Lost when there is none. What is of the same
+    /// kind is up to `TxQueue::with_same_kind`, nothing by default.
+    Coalesce,
+}
+
+/// The same kind of command for `Overflow::Coalesce`: the same variant, for the same
+/// channel, the same step of the uploaded pattern.
+pub fn same_kind(new: &Command, waiting: &Command) -> bool {
+    match (new, waiting) {
+        (
+            Command::Channel { channel, command },
+            Command::Channel {
+                channel: waiting_channel,
+                command: waiting_command,
+            },
+        ) => channel == waiting_channel && discriminant(command) == discriminant(waiting_command),
+        (Command::Step { index, .. }, Command::Step { index: waiting, .. }) => index == waiting,
+        _ => discriminant(new) == discriminant(waiting),
+    }
+}
+
+/// Every message matters, none replaces another.
+fn never<T>(_: &T, _: &T) -> bool {
+    false
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TxStats {
+    /// Frames handed to the UART.
+    pub sent: u32,
+    /// Messages lost because the queue was full.
+    pub dropped: u32,
+    /// Messages that replaced a waiting one, see `Overflow::Coalesce`.
+    pub coalesced: u32,
+    /// The most messages that waited at the same time.
+    pub max_depth: u8,
+}
+
+pub struct TxQueue<T, const D: usize> {
+    waiting: [Option<T>; D],
+    head: usize,
+    len: usize,
+    overflow: Overflow,
+    same: fn(&T, &T) -> bool,
+    // The frame going out, for `next_byte`
+    frame: [u8; MAX_FRAME_LEN],
+    frame_len: usize,
+    frame_pos: usize,
+    pub stats: TxStats,
+}
+
+impl<T: Copy + Serialize, const D: usize> TxQueue<T, D> {
+    pub const fn new(overflow: Overflow) -> Self {
+        TxQueue {
+            waiting: [None; D],
+            head: 0,
+            len: 0,
+            overflow,
+            same: never,
+            frame: [0; MAX_FRAME_LEN],
+            frame_len: 0,
+            frame_pos: 0,
+            stats: TxStats {
+                sent: 0,
+                dropped: 0,
+                coalesced: 0,
+                max_depth: 0,
+            },
+        }
+    }
+
+    /// Whether a new message may replace a waiting one with `Overflow::Coalesce`, like
+    /// `same_kind` for the commands.
+    pub const fn with_same_kind(mut self, same: fn(new: &T, waiting: &T) -> bool) -> Self {
+        self.same = same;
+        self
+    }
+
+    /// Queues `msg`, false if it, or an older one, was dropped to make room.
+    pub fn push(&mut self, msg: T) -> bool {
+        if self.len == D {
+            match self.overflow {
+                Overflow::DropOldest => {
+                    self.pop();
+                    self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                    self.append(msg);
+                    return false;
+                }
+                Overflow::DropNewest => {
+                    self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                    return false;
+                }
+                Overflow::Coalesce => {
+                    let same = (0..self.len)
+                        .map(|i| (self.head + i) % D)
+                        .find(|&at| self.waiting[at].is_some_and(|w| (self.same)(&msg, &w)));
+                    match same {
+                        Some(at) => {
+                            self.waiting[at] = Some(msg);
+                            self.stats.coalesced = self.stats.coalesced.wrapping_add(1);
+                            return true;
+                        }
+                        None => {
+                            self.stats.dropped = self.stats.dropped.wrapping_add(1);
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        self.append(msg);
+        true
+    }
+
+    fn append(&mut self, msg: T) {
+        self.waiting[(self.head + self.len) % D] = Some(msg);
+        self.len += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.len as u8);
+    }
+
+    /// How many messages wait, the frame going out not included.
+    pub fn depth(&self) -> usize {
+        self.len
+    }
+
+    /// Nothing waits and the last frame is out.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0 && self.frame_pos == self.frame_len
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let msg = self.waiting[self.head].take();
+        self.head = (self.head + 1) % D;
+        self.len -= 1;
+        msg
+    }
+
+    /// The next frame, encoded. It stays valid until the next call.
+    pub fn next_frame(&mut self) -> Option<&[u8]> {
+        let msg = self.pop()?;
+        // Every message of the protocol fits in MAX_FRAME_LEN
+        self.frame_len = frame::encode(&msg, &mut self.frame).ok()?.len();
+        self.frame_pos = self.frame_len;
+        self.stats.sent = self.stats.sent.wrapping_add(1);
+        Some(&self.frame[..self.frame_len])
+    }
+
+    /// The next byte of the frame going out, or of the next one.
+    pub fn next_byte(&mut self) -> Option<u8> {
+        if self.frame_pos == self.frame_len {
+            self.next_frame()?;
+            self.frame_pos = 0;
+        }
+        let byte = self.frame[self.frame_pos];
+        self.frame_pos += 1;
+        Some(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::Command;
+
+    fn drain<const D: usize>(queue: &mut TxQueue<Command, D>) -> std::vec::Vec<Command> {
+        let mut sent = std::vec::Vec::new();
+        while let Some(frame) = queue.next_frame() {
+            let mut frame = frame.to_vec();
+            let len = frame.len() - 1;
+            sent.push(frame::decode(&mut frame[..len]).unwrap());
+        }
+        sent
+    }
+
+    #[test]
+    fn first_in_first_out() {
+        let mut queue = TxQueue::<Command, 4>::new(Overflow::DropNewest);
+        assert!(queue.is_empty());
+        assert!(queue.push(Command::On));
+        assert!(queue.push(Command::Pwm(3)));
+        assert_eq!(queue.depth(), 2);
+        assert_eq!(drain(&mut queue), [Command::On, Command::Pwm(3)]);
+        assert_eq!(queue.stats.sent, 2);
+        assert_eq!(queue.stats.max_depth, 2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn overflow_policies() {
+        let full = |overflow| {
+            let mut queue = TxQueue::<Command, 2>::new(overflow);
+            queue.push(Command::Pwm(1));
+            queue.push(Command::Interval(1));
+            queue
+        };
+
+        let mut queue = full(Overflow::DropOldest);
+        assert!(!queue.push(Command::Off));
+        assert_eq!(drain(&mut queue), [Command::Interval(1), Command::Off]);
+        assert_eq!(queue.stats.dropped, 1);
+
+        let mut queue = full(Overflow::DropNewest);
+        assert!(!queue.push(Command::Off));
+        assert_eq!(drain(&mut queue), [Command::Pwm(1), Command::Interval(1)]);
+        assert_eq!(queue.stats.dropped, 1);
+
+        let mut queue = full(Overflow::Coalesce).with_same_kind(same_kind);
+        assert!(queue.push(Command::Pwm(3)));
+        assert!(!queue.push(Command::Off));
+        assert_eq!(drain(&mut queue), [Command::Pwm(3), Command::Interval(1)]);
+        assert_eq!((queue.stats.coalesced, queue.stats.dropped), (1, 1));
+    }
+
+    #[test]
+    fn coalesces_the_same_kind_only() {
+        use protocol::ChannelCommand;
+
+        let channel = |channel, command| Command::Channel { channel, command };
+        let step = |index, level| Command::Step {
+            index,
+            level,
+            duration_ms: 100,
+        };
+        let mut queue = TxQueue::<Command, 3>::new(Overflow::Coalesce).with_same_kind(same_kind);
+        queue.push(channel(2, ChannelCommand::Pwm(1)));
+        queue.push(step(0, 255));
+        queue.push(channel(1, ChannelCommand::Stop));
+        // another channel, another step, another command of the channel: all lost
+        assert!(!queue.push(channel(3, ChannelCommand::Pwm(9))));
+        assert!(!queue.push(step(1, 0)));
+        assert!(!queue.push(channel(2, ChannelCommand::On)));
+        assert_eq!(queue.stats.dropped, 3);
+        assert!(queue.push(channel(2, ChannelCommand::Pwm(9))));
+        assert!(queue.push(step(0, 128)));
+        assert_eq!(
+            drain(&mut queue),
+            [
+                channel(2, ChannelCommand::Pwm(9)),
+                step(0, 128),
+                channel(1, ChannelCommand::Stop)
+            ]
+        );
+        assert_eq!(queue.stats.coalesced, 2);
+
+        // without a kind, nothing is replaced
+        let mut queue = TxQueue::<Command, 1>::new(Overflow::Coalesce);
+        queue.push(Command::Pwm(1));
+        assert!(!queue.push(Command::Pwm(2)));
+        assert_eq!(drain(&mut queue), [Command::Pwm(1)]);
+    }
+
+    #[test]
+    fn byte_by_byte() {
+        let mut queue = TxQueue::<Command, 2>::new(Overflow::DropOldest);
+        queue.push(Command::On);
+        queue.push(Command::Pwm(7));
+        let bytes: std::vec::Vec<u8> = core::iter::from_fn(|| queue.next_byte()).collect();
+        let mut expected = [0; 2 * MAX_FRAME_LEN];
+        let first = frame::encode(&Command::On, &mut expected).unwrap().len();
+        let second = frame::encode(&Command::Pwm(7), &mut expected[first..])
+            .unwrap()
+            .len();
+        assert_eq!(bytes, expected[..first + second]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats.sent, 2);
+    }
+}
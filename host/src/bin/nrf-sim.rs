@@ -0,0 +1,114 @@
+//! A virtual nRF52 running `interval_08`, for when there is no DK on the bench.
+//!
+//! ```terminal
+//! nrf-sim [--device <path>] [<script>]
+//! ```
+//!
+//! Every line of the script (or of the keyboard, without a script) presses buttons:
+//! `up` and `down` change the interval, `on` and `off` the brightness, `wait <ms>` waits.
+//! Without `--device`, it creates a pseudo-terminal and prints its path: point
+//! `nucleo-sim` or the `sniffer` to it. A heartbeat is sent every 500 ms, like the firmware.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader},
+    process,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use appcore::remote::{Remote, FINE_STEP};
+use host::{hex, open, send, to_frame, BAUD_RATE};
+use protocol::{link::HEARTBEAT_PERIOD, Command};
+use serialport::{SerialPort, TTYPort};
+
+const USAGE: &str = "usage: nrf-sim [--device <path>] [<script>]
+buttons: up | down | on | off, and wait <ms>";
+
+fn fail(msg: String) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+fn transmit(port: &Mutex<TTYPort>, command: &Command) {
+    if let Err(e) = send(&mut *port.lock().unwrap(), command) {
+        fail(format!("could not write: {}", e));
+    }
+}
+
+/// Runs one line of the script.
+fn press(line: &str, remote: &mut Remote, port: &Mutex<TTYPort>) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    while let Some(word) = words.next() {
+        // A word is a press of that button, as `gesture::Button` recognizes it.
+        let command = match word {
+            "up" => remote.interval(true, false),
+            "down" => remote.interval(false, true),
+            "on" => remote.brightness(true, false),
+            "off" => remote.brightness(false, true),
+            "wait" => {
+                let ms = words.next().and_then(|ms| ms.parse().ok());
+                let ms = ms.ok_or(format!("wait needs milliseconds\n{}", USAGE))?;
+                thread::sleep(Duration::from_millis(ms));
+                continue;
+            }
+            _ => return Err(format!("unknown button {}\n{}", word, USAGE)),
+        };
+        println!("> {:?} [{}]", command, hex(&to_frame(&command)));
+        transmit(port, &command);
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut device = None;
+    let mut script = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" => device = Some(args.next().unwrap_or_else(|| fail(USAGE.into()))),
+            "-h" | "--help" => fail(USAGE.into()),
+            _ => script = Some(arg),
+        }
+    }
+
+    // The other end of the pty must stay open, or writes fail until someone connects.
+    let (port, _other_end) = match device {
+        Some(path) => {
+            let port = open(&path, BAUD_RATE, Duration::from_millis(100))
+                .unwrap_or_else(|e| fail(format!("could not open {}: {}", path, e)));
+            (port, None)
+        }
+        None => {
+            let (ours, theirs) =
+                TTYPort::pair().unwrap_or_else(|e| fail(format!("could not create a pty: {}", e)));
+            println!("virtual nRF52 on {}", theirs.name().unwrap());
+            (ours, Some(theirs))
+        }
+    };
+    let port = Arc::new(Mutex::new(port));
+
+    let heartbeat = Arc::clone(&port);
+    thread::spawn(move || loop {
+        transmit(&heartbeat, &Command::Heartbeat);
+        thread::sleep(Duration::from_micros(HEARTBEAT_PERIOD.to_micros().into()));
+    });
+
+    let input: Box<dyn BufRead> = match script {
+        Some(path) => {
+            Box::new(BufReader::new(fs::File::open(&path).unwrap_or_else(|e| {
+                fail(format!("could not open {}: {}", path, e))
+            })))
+        }
+        None => Box::new(io::stdin().lock()),
+    };
+    let mut remote = Remote::new(FINE_STEP);
+    for line in input.lines() {
+        let line = line.unwrap_or_else(|e| fail(format!("could not read: {}", e)));
+        let line = line.split('#').next().unwrap();
+        if let Err(e) = press(line, &mut remote, &port) {
+            eprintln!("{}", e);
+        }
+    }
+}
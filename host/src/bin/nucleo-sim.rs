@@ -0,0 +1,125 @@
+//! A virtual Nucleo running `interval_08`, for when there is no STM32 on the bench.
+//!
+//! ```terminal
+//! nucleo-sim [--device <path>] [--for <secs>]
+//! ```
+//!
+//! Without `--device`, it creates a pseudo-terminal and prints its path: point the
+//! nRF52 simulator or `uartctl` to it. Every blink prints the duty of the led.
+
+use std::{
+    io::{self, Read},
+    process,
+    time::{Duration, Instant},
+};
+
+use appcore::blinker::Blinker;
+use host::{mono_now, open, BAUD_RATE};
+use protocol::{
+    accumulator::{inter_byte_timeout, Accumulator, Feed},
+    link::{Transition, HEARTBEAT_PERIOD},
+};
+use serialport::{SerialPort, TTYPort};
+
+/// `get_max_duty()` of the pwm channel of `interval_08`: 48 MHz / 20 kHz.
+const MAX_DUTY: u16 = 2400;
+
+const USAGE: &str = "usage: nucleo-sim [--device <path>] [--for <secs>]";
+
+fn fail(msg: String) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+fn log(boot: Instant, what: &str) {
+    println!("{:>8.3}s {}", boot.elapsed().as_secs_f64(), what);
+}
+
+fn main() {
+    let mut device = None;
+    let mut run_for = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--device" => device = Some(args.next().unwrap_or_else(|| fail(USAGE.into()))),
+            "--for" => {
+                let secs = args.next().and_then(|s| s.parse().ok());
+                run_for = Some(Duration::from_secs_f64(
+                    secs.unwrap_or_else(|| fail(USAGE.into())),
+                ));
+            }
+            _ => fail(USAGE.into()),
+        }
+    }
+
+    // The other end of the pty must stay open, or reads fail once nobody is connected.
+    let (mut port, _other_end) = match device {
+        Some(path) => {
+            let port = open(&path, BAUD_RATE, Duration::from_millis(5))
+                .unwrap_or_else(|e| fail(format!("could not open {}: {}", path, e)));
+            (port, None)
+        }
+        None => {
+            let (mut ours, theirs) =
+                TTYPort::pair().unwrap_or_else(|e| fail(format!("could not create a pty: {}", e)));
+            ours.set_timeout(Duration::from_millis(5)).unwrap();
+            println!("virtual Nucleo on {}", theirs.name().unwrap());
+            (ours, Some(theirs))
+        }
+    };
+
+    let boot = Instant::now();
+    let mut frames: Accumulator =
+        Accumulator::new().with_timeout(inter_byte_timeout(BAUD_RATE, 10));
+    let mut blinker = Blinker::new(MAX_DUTY);
+    let mut next_blink = boot;
+    let mut next_supervise = boot;
+    let mut buf = [0u8; 64];
+
+    while run_for.is_none_or(|d| boot.elapsed() < d) {
+        let n = match port.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => 0,
+            Err(e) => fail(format!("could not read: {}", e)),
+        };
+        for &d in &buf[..n] {
+            match frames.feed_at(d, mono_now(boot)) {
+                Feed::Frame(command) => {
+                    log(boot, &format!("< {:?}", command));
+                    if let Some(transition) = blinker.on_command(command, mono_now(boot)) {
+                        log_link(boot, transition);
+                    }
+                }
+                Feed::Consumed => {}
+                dropped => log(
+                    boot,
+                    &format!(
+                        "dropped a frame ({:?}), {} so far",
+                        dropped,
+                        frames.stats.dropped()
+                    ),
+                ),
+            }
+        }
+
+        let now = Instant::now();
+        if now >= next_supervise {
+            if let Some(transition) = blinker.link.poll(mono_now(boot)) {
+                log_link(boot, transition);
+            }
+            next_supervise += Duration::from_micros(HEARTBEAT_PERIOD.to_micros().into());
+        }
+        if now >= next_blink {
+            let blink = blinker.blink(mono_now(boot));
+            log(boot, &format!("led {}/{}", blink.duty, MAX_DUTY));
+            next_blink += Duration::from_micros(blink.next_in.to_micros().into());
+        }
+    }
+}
+
+fn log_link(boot: Instant, transition: Transition) {
+    match transition {
+        Transition::Up => log(boot, "link up"),
+        Transition::Down => log(boot, "link down, failsafe"),
+    }
+}
@@ -0,0 +1,94 @@
+//! Prints the frames of a capture of the wire, and why the bad ones are bad.
+//!
+//! ```terminal
+//! sniffer [--wire commands] <capture file>
+//! sniffer [--wire commands] --live [--baud 9600] <device>
+//! ```
+//!
+//! A capture file holds the raw bytes, as saved by a USB-serial adapter or exported
+//! by a logic analyser. Live records are timestamped from the start of the capture.
+//! `--wire` says which side of the link sent the frames, see `host::Wire`: the
+//! `commands` or `requests` of the nRF52, or the `answers` of the Nucleo.
+
+use std::{
+    fs,
+    io::{self, Read},
+    process,
+    time::{Duration, Instant},
+};
+
+use host::{hex, open, sniff::Sniffer, Wire, BAUD_RATE};
+
+const USAGE: &str = "usage: sniffer [--wire <commands | requests | answers>] <capture file>
+       sniffer [--wire <commands | requests | answers>] --live [--baud <rate>] <device>";
+
+fn fail(msg: String) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
+
+fn print_file(path: &str, wire: Wire) {
+    let capture =
+        fs::read(path).unwrap_or_else(|e| fail(format!("could not read {}: {}", path, e)));
+    let mut sniffer = Sniffer::new(wire);
+    for record in sniffer.push(&capture, None) {
+        println!("{}", record);
+    }
+    if !sniffer.leftover().is_empty() {
+        println!(
+            "unterminated frame at the end: [{}]",
+            hex(sniffer.leftover())
+        );
+    }
+}
+
+fn print_live(path: &str, baud: u32, wire: Wire) -> ! {
+    let mut port = open(path, baud, Duration::from_millis(100))
+        .unwrap_or_else(|e| fail(format!("could not open {}: {}", path, e)));
+    let start = Instant::now();
+    let mut sniffer = Sniffer::new(wire);
+    let mut buf = [0u8; 64];
+    loop {
+        match port.read(&mut buf) {
+            Ok(n) => {
+                for record in sniffer.push(&buf[..n], Some(start.elapsed())) {
+                    println!("{}", record);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+            Err(e) => fail(format!("could not read from {}: {}", path, e)),
+        }
+    }
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+    let mut wire = Wire::default();
+    if args.peek().map(String::as_str) == Some("--wire") {
+        args.next();
+        wire = args
+            .next()
+            .and_then(|w| w.parse().ok())
+            .unwrap_or_else(|| fail(USAGE.into()));
+    }
+    match args.next().as_deref() {
+        Some("--live") => {
+            let mut baud = BAUD_RATE;
+            let mut device = None;
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--baud" => {
+                        baud = args
+                            .next()
+                            .and_then(|b| b.parse().ok())
+                            .unwrap_or_else(|| fail(USAGE.into()))
+                    }
+                    _ => device = Some(arg),
+                }
+            }
+            print_live(&device.unwrap_or_else(|| fail(USAGE.into())), baud, wire)
+        }
+        Some("-h") | Some("--help") | None => fail(USAGE.into()),
+        Some(path) => print_file(path, wire),
+    }
+}
@@ -0,0 +1,202 @@
+//! Sends commands to the Nucleo from the computer, in place of the nRF52.
+//!
+//! ```terminal
+//! uartctl [--baud 9600] [--listen 200] [--wire answers] <device> <command>...
+//! ```
+//!
+//! with `<command>` one of `on`, `off`, `pwm <level>`, `interval <secs>`, `heartbeat`,
+//! `fade <level> <ms> <easing>`, `play <pattern> <repeat>`, `step <index> <level> <ms>`,
+//! `morse <text> <repeat>`, `stop`, `blink <on ms> <off ms>`, `blink-duty <ms> <percent>`,
+//! `channel <n> <command>` and `rgb <red> <green> <blue>`.
+//! The blink times are clamped to what the Nucleo can show, 0 is steadily on.
+//! A `channel` takes `on`, `off`, `pwm`, `fade`, `play`, `stop` or `blink`, for its led only.
+//! The frames that come back within `--listen` milliseconds are printed, as the answers
+//! of a Nucleo unless `--wire` says they are the `commands` or `requests` of an nRF52.
+
+use std::{process, str::FromStr, time::Duration};
+
+use host::{describe, hex, open, read_frames, send, to_frame, Wire, BAUD_RATE};
+use protocol::{clamp_blink_ms, ChannelCommand, Command, Easing, Pattern, MORSE_LEN};
+
+const USAGE: &str = "usage: uartctl [--baud <rate>] [--listen <ms>]
+               [--wire <answers | commands | requests>] <device> <command>...
+commands: on | off | pwm <0-255> | interval <0-255> | heartbeat
+          | fade <0-255> <0-65535 ms> <linear | in | out | in-out | exp>
+          | play <breathing | heartbeat | strobe | uploaded> <0-255, 0 for ever>
+          | step <0-255> <0-255> <0-65535 ms> | morse <text> <0-255> | stop
+          | blink <on ms> <off ms> | blink-duty <period ms> <0-100 %>
+          | channel <0-255> <on | off | pwm | fade | play | stop | blink> ...
+          | rgb <0-255> <0-255> <0-255>";
+
+struct Args {
+    baud: u32,
+    listen: Duration,
+    wire: Wire,
+    device: String,
+    commands: Vec<Command>,
+}
+
+fn number<T: FromStr>(what: &str, arg: Option<String>) -> Result<T, String> {
+    let arg = arg.ok_or(format!("{} needs a value", what))?;
+    arg.parse()
+        .map_err(|_| format!("{} is not a valid value for {}", arg, what))
+}
+
+fn easing(arg: Option<String>) -> Result<Easing, String> {
+    match arg.as_deref() {
+        Some("linear") => Ok(Easing::Linear),
+        Some("in") => Ok(Easing::EaseIn),
+        Some("out") => Ok(Easing::EaseOut),
+        Some("in-out") => Ok(Easing::EaseInOut),
+        Some("exp") => Ok(Easing::Exponential),
+        Some(arg) => Err(format!("{} is not a valid value for the easing", arg)),
+        None => Err("fade needs an easing".into()),
+    }
+}
+
+fn pattern(arg: Option<String>) -> Result<Pattern, String> {
+    match arg.as_deref() {
+        Some("breathing") => Ok(Pattern::Breathing),
+        Some("heartbeat") => Ok(Pattern::Heartbeat),
+        Some("strobe") => Ok(Pattern::Strobe),
+        Some("uploaded") => Ok(Pattern::Uploaded),
+        Some(arg) => Err(format!("{} is not a valid value for the pattern", arg)),
+        None => Err("play needs a pattern".into()),
+    }
+}
+
+fn text(arg: Option<String>) -> Result<String, String> {
+    match arg {
+        Some(text) if text.is_ascii() && text.len() <= MORSE_LEN => Ok(text),
+        Some(text) => Err(format!(
+            "{} is not {} ASCII characters or less",
+            text, MORSE_LEN
+        )),
+        None => Err("morse needs a text".into()),
+    }
+}
+
+/// The command after `channel <n>`, with the arguments of the command of that name.
+fn channel_command(args: &mut impl Iterator<Item = String>) -> Result<ChannelCommand, String> {
+    let arg = args.next().ok_or("channel needs a command")?;
+    Ok(match arg.as_str() {
+        "on" => ChannelCommand::On,
+        "off" => ChannelCommand::Off,
+        "pwm" => ChannelCommand::Pwm(number("pwm", args.next())?),
+        "fade" => ChannelCommand::Fade {
+            target: number("fade", args.next())?,
+            duration_ms: number("fade", args.next())?,
+            easing: easing(args.next())?,
+        },
+        "play" => ChannelCommand::Play {
+            pattern: pattern(args.next())?,
+            repeat: number("play", args.next())?,
+        },
+        "stop" => ChannelCommand::Stop,
+        "blink" => ChannelCommand::Blink {
+            on_ms: clamp_blink_ms(number("blink", args.next())?),
+            off_ms: clamp_blink_ms(number("blink", args.next())?),
+        },
+        _ => return Err(format!("{} is not a command of a channel\n{}", arg, USAGE)),
+    })
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut baud = BAUD_RATE;
+    let mut listen = Duration::from_millis(200);
+    let mut wire = Wire::Answers;
+    let mut device = None;
+    let mut commands = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--baud" => baud = number("--baud", args.next())?,
+            "--listen" => listen = Duration::from_millis(number("--listen", args.next())?),
+            "--wire" => wire = number("--wire", args.next())?,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if device.is_none() => device = Some(arg),
+            "on" => commands.push(Command::On),
+            "off" => commands.push(Command::Off),
+            "pwm" => commands.push(Command::Pwm(number("pwm", args.next())?)),
+            "interval" => commands.push(Command::Interval(number("interval", args.next())?)),
+            "heartbeat" => commands.push(Command::Heartbeat),
+            "fade" => commands.push(Command::Fade {
+                target: number("fade", args.next())?,
+                duration_ms: number("fade", args.next())?,
+                easing: easing(args.next())?,
+            }),
+            "play" => commands.push(Command::Play {
+                pattern: pattern(args.next())?,
+                repeat: number("play", args.next())?,
+            }),
+            "step" => commands.push(Command::Step {
+                index: number("step", args.next())?,
+                level: number("step", args.next())?,
+                duration_ms: number("step", args.next())?,
+            }),
+            "morse" => {
+                let text = text(args.next())?;
+                commands.push(Command::morse(&text, number("morse", args.next())?));
+            }
+            "stop" => commands.push(Command::Stop),
+            "blink" => commands.push(Command::blink(
+                number("blink", args.next())?,
+                number("blink", args.next())?,
+            )),
+            "blink-duty" => commands.push(Command::blink_duty(
+                number("blink-duty", args.next())?,
+                number("blink-duty", args.next())?,
+            )),
+            "channel" => commands.push(Command::Channel {
+                channel: number("channel", args.next())?,
+                command: channel_command(&mut args)?,
+            }),
+            "rgb" => commands.push(Command::Rgb {
+                red: number("rgb", args.next())?,
+                green: number("rgb", args.next())?,
+                blue: number("rgb", args.next())?,
+            }),
+            _ => return Err(format!("unknown command {}\n{}", arg, USAGE)),
+        }
+    }
+    let device = device.ok_or(USAGE)?;
+    if commands.is_empty() {
+        return Err(USAGE.into());
+    }
+    Ok(Args {
+        baud,
+        listen,
+        wire,
+        device,
+        commands,
+    })
+}
+
+fn main() {
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let mut port = open(&args.device, args.baud, args.listen).unwrap_or_else(|e| {
+        eprintln!("could not open {}: {}", args.device, e);
+        process::exit(1);
+    });
+
+    for command in &args.commands {
+        println!("> {:?} [{}]", command, hex(&to_frame(command)));
+        if let Err(e) = send(&mut port, command) {
+            eprintln!("could not write to {}: {}", args.device, e);
+            process::exit(1);
+        }
+    }
+    match read_frames(&mut port) {
+        Ok(frames) => {
+            for frame in frames {
+                println!("< {}", describe(&frame, args.wire));
+            }
+        }
+        Err(e) => {
+            eprintln!("could not read from {}: {}", args.device, e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! What the host tools have in common: opening a serial port (a real one or a
+//! pseudo-terminal), and turning bytes into frames and frames into something readable.
+
+pub mod mock;
+pub mod sniff;
+pub mod transport;
+
+use std::{fmt, io, str::FromStr, time::Duration};
+
+use protocol::{
+    frame,
+    reliable::{Reply, Request},
+    Command, Error, Response, MAX_FRAME_LEN,
+};
+use serde::Serialize;
+use serialport::{SerialPort, TTYPort};
+
+/// The baud rate both boards use.
+pub const BAUD_RATE: u32 = 9600;
+
+/// `monotonics::now()` of a board that booted at `boot`, for the state machines.
+pub fn mono_now(boot: std::time::Instant) -> protocol::Instant {
+    // Wraps after ~71 minutes, like the 32 bits monotonic timers of the boards.
+    protocol::Instant::from_ticks(boot.elapsed().as_micros() as u32)
+}
+
+/// Opens a serial device, or the slave side of a pty, in raw mode.
+/// Reads time out after `timeout`, so the tools can stop listening.
+pub fn open(path: &str, baud: u32, timeout: Duration) -> serialport::Result<TTYPort> {
+    serialport::new(path, baud).timeout(timeout).open_native()
+}
+
+/// The bytes to write on the wire for `msg`, exactly like the firmware encodes them.
+pub fn to_frame<T: Serialize>(msg: &T) -> Vec<u8> {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    frame::encode(msg, &mut buf)
+        .expect("every message fits in MAX_FRAME_LEN")
+        .to_vec()
+}
+
+/// Writes `msg` as one frame.
+pub fn send<T: Serialize>(port: &mut impl io::Write, msg: &T) -> io::Result<()> {
+    port.write_all(&to_frame(msg))?;
+    port.flush()
+}
+
+/// Cuts a stream of bytes into frames, on every 0.
+#[derive(Default)]
+pub struct Splitter {
+    partial: Vec<u8>,
+}
+
+impl Splitter {
+    /// Returns the frames completed by `bytes`, without their terminating 0.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        for &b in bytes {
+            if b == 0 {
+                if !self.partial.is_empty() {
+                    frames.push(std::mem::take(&mut self.partial));
+                }
+            } else {
+                self.partial.push(b);
+            }
+        }
+        frames
+    }
+
+    /// The bytes of a frame that is not terminated yet.
+    pub fn partial(&self) -> &[u8] {
+        &self.partial
+    }
+}
+
+/// Bytes as `01 03 f0`, like in `protocol/tests/snapshots/wire.txt`.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What a frame holds, once its `Wire` is known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Command(Command),
+    Request(Request),
+    Response(Response),
+    Reply(Reply),
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Message::Command(command) => write!(f, "Command::{:?}", command),
+            Message::Request(request) => write!(f, "{:?}", request),
+            Message::Response(response) => write!(f, "Response::{:?}", response),
+            Message::Reply(reply) => write!(f, "{:?}", reply),
+        }
+    }
+}
+
+/// The side of the link a frame came from. Frames do not say what they hold, the same
+/// bytes are `Command::Pwm(0)` from the nRF52 and `Reply { seq: 2, response: Ack }`
+/// from the Nucleo: only the wire tells.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Wire {
+    /// From the nRF52, or `uartctl`: the `Command`s of programs #6 to #8.
+    #[default]
+    Commands,
+    /// From the nRF52 of `reliable_09`: the `Request`s.
+    Requests,
+    /// From the Nucleo: a `Response`, or the `Reply` of `reliable_09`, one byte longer.
+    Answers,
+}
+
+impl Wire {
+    /// Decodes `frame` (without its terminating 0) as the message of this wire.
+    pub fn decode(self, frame: &[u8]) -> Result<Message, Error> {
+        let mut frame = frame.to_vec();
+        match self {
+            Wire::Commands => frame::decode(&mut frame).map(Message::Command),
+            Wire::Requests => frame::decode(&mut frame).map(Message::Request),
+            Wire::Answers => match frame::decode(&mut frame.clone()) {
+                Err(Error::Postcard(postcard::Error::DeserializeUnexpectedEnd)) => {
+                    frame::decode(&mut frame).map(Message::Response)
+                }
+                reply => reply.map(Message::Reply),
+            },
+        }
+    }
+}
+
+impl FromStr for Wire {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commands" => Ok(Wire::Commands),
+            "requests" => Ok(Wire::Requests),
+            "answers" => Ok(Wire::Answers),
+            _ => Err(format!("{} is not commands, requests or answers", s)),
+        }
+    }
+}
+
+/// A frame (without its terminating 0) of `wire` as a human would like to read it.
+pub fn describe(frame: &[u8], wire: Wire) -> String {
+    match wire.decode(frame) {
+        Ok(message) => message.to_string(),
+        Err(e) => format!("{:?} [{}]", e, hex(frame)),
+    }
+}
+
+/// Reads until the port is silent for its timeout, and returns the frames that came.
+pub fn read_frames(port: &mut dyn SerialPort) -> io::Result<Vec<Vec<u8>>> {
+    let mut splitter = Splitter::default();
+    let mut frames = Vec::new();
+    let mut buf = [0u8; 64];
+    loop {
+        match port.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => frames.extend(splitter.push(&buf[..n])),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(frames)
+}
@@ -0,0 +1,206 @@
+//! A board made of plain values, for the tests: press its buttons, fill its UART,
+//! move its clock, and look at what the example did with the led.
+
+use std::{collections::VecDeque, convert::Infallible};
+
+use appcore::board::Board;
+use embedded_hal::{
+    digital::v2::{InputPin, OutputPin},
+    serial, PwmPin,
+};
+use protocol::{Duration, Instant};
+use rtic_monotonic::Monotonic;
+
+#[derive(Default)]
+pub struct MockLed {
+    pub on: bool,
+}
+
+impl OutputPin for MockLed {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.on = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.on = true;
+        Ok(())
+    }
+}
+
+/// Pulls its pin to the ground while pressed, like the buttons of both boards.
+#[derive(Default)]
+pub struct MockButton {
+    pub pressed: bool,
+}
+
+impl InputPin for MockButton {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(!self.pressed)
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(self.pressed)
+    }
+}
+
+/// Keeps every byte written.
+#[derive(Default)]
+pub struct MockTx {
+    pub sent: Vec<u8>,
+}
+
+impl serial::Write<u8> for MockTx {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.sent.push(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// Gives the bytes pushed to `incoming`, then would block.
+#[derive(Default)]
+pub struct MockRx {
+    pub incoming: VecDeque<u8>,
+}
+
+impl serial::Read<u8> for MockRx {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        self.incoming.pop_front().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+pub struct MockPwm {
+    pub duty: u16,
+    pub max_duty: u16,
+    pub enabled: bool,
+}
+
+impl PwmPin for MockPwm {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.enabled = false;
+    }
+
+    fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.duty
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        self.max_duty
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.duty = duty;
+    }
+}
+
+/// Only moves when told to, from 0.
+pub struct MockClock {
+    pub now: Instant,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        MockClock {
+            now: Instant::from_ticks(0),
+        }
+    }
+}
+
+impl MockClock {
+    pub fn advance(&mut self, by: Duration) {
+        self.now += by;
+    }
+}
+
+impl Monotonic for MockClock {
+    type Instant = Instant;
+    type Duration = Duration;
+
+    fn now(&mut self) -> Instant {
+        self.now
+    }
+
+    fn set_compare(&mut self, _instant: Instant) {}
+
+    fn clear_compare_flag(&mut self) {}
+
+    fn zero() -> Instant {
+        Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {}
+}
+
+pub struct MockBoard {
+    pub led: MockLed,
+    pub buttons: Vec<MockButton>,
+    pub tx: MockTx,
+    pub rx: MockRx,
+    pub pwm: MockPwm,
+    pub clock: MockClock,
+}
+
+impl MockBoard {
+    /// A board with `buttons` buttons, none pressed, and an enabled pwm channel.
+    pub fn new(buttons: usize, max_duty: u16) -> Self {
+        MockBoard {
+            led: MockLed::default(),
+            buttons: (0..buttons).map(|_| MockButton::default()).collect(),
+            tx: MockTx::default(),
+            rx: MockRx::default(),
+            pwm: MockPwm {
+                duty: 0,
+                max_duty,
+                enabled: true,
+            },
+            clock: MockClock::default(),
+        }
+    }
+
+    /// What the Nucleo would have received from the nRF52.
+    pub fn receive(&mut self, bytes: &[u8]) {
+        self.rx.incoming.extend(bytes);
+    }
+}
+
+impl Board for MockBoard {
+    type Led = MockLed;
+    type Button = MockButton;
+    type Tx = MockTx;
+    type Rx = MockRx;
+    type Pwm = MockPwm;
+
+    fn led(&mut self) -> &mut MockLed {
+        &mut self.led
+    }
+
+    fn buttons(&mut self) -> &mut [MockButton] {
+        &mut self.buttons
+    }
+
+    fn uart(&mut self) -> (&mut MockTx, &mut MockRx) {
+        (&mut self.tx, &mut self.rx)
+    }
+
+    fn pwm(&mut self) -> &mut MockPwm {
+        &mut self.pwm
+    }
+}
@@ -0,0 +1,102 @@
+//! Turns a captured byte stream into readable records, one per frame.
+
+use std::{fmt, time::Duration};
+
+use protocol::Error;
+
+use crate::{hex, Message, Wire};
+
+/// One frame of the capture.
+pub struct Record {
+    /// Number of the frame in the capture, from 0.
+    pub index: usize,
+    /// Offset of its first byte in the capture.
+    pub offset: usize,
+    /// When its terminating 0 was read, for live captures.
+    pub at: Option<Duration>,
+    /// The bytes of the frame, without the terminating 0.
+    pub bytes: Vec<u8>,
+    pub decoded: Result<Message, Error>,
+}
+
+/// Why a frame could not be decoded, in words.
+pub fn reason(e: &Error) -> String {
+    match e {
+        Error::Cobs => "COBS error".into(),
+        Error::Crc => "bad CRC".into(),
+        Error::TrailingBytes => "trailing bytes".into(),
+        // serde reports an out of range variant index as a custom error
+        Error::Postcard(postcard::Error::DeserializeBadEnum)
+        | Error::Postcard(postcard::Error::SerdeDeCustom) => "unknown variant".into(),
+        Error::Postcard(postcard::Error::DeserializeUnexpectedEnd) => "too short".into(),
+        Error::Postcard(e) => format!("{:?}", e),
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.at {
+            Some(at) => write!(f, "{:>10.3}s ", at.as_secs_f64())?,
+            None => write!(f, "{:>11} ", "")?,
+        }
+        write!(f, "#{:<4} @{:<6} ", self.index, self.offset)?;
+        match &self.decoded {
+            Ok(message) => write!(f, "{}", message)?,
+            Err(e) => write!(f, "BAD ({})", reason(e))?,
+        }
+        write!(f, " [{} 00]", hex(&self.bytes))
+    }
+}
+
+/// Splits the capture on the COBS delimiters and decodes every frame as a message of
+/// its `Wire`, `Command`s unless told otherwise.
+#[derive(Default)]
+pub struct Sniffer {
+    wire: Wire,
+    partial: Vec<u8>,
+    /// Offset of the next byte in the capture.
+    offset: usize,
+    frames: usize,
+}
+
+impl Sniffer {
+    /// For a capture of `wire`.
+    pub fn new(wire: Wire) -> Self {
+        Sniffer {
+            wire,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a record for every frame terminated in `bytes`.
+    /// `at` is when the bytes were read, if known.
+    pub fn push(&mut self, bytes: &[u8], at: Option<Duration>) -> Vec<Record> {
+        let mut records = Vec::new();
+        for &b in bytes {
+            self.offset += 1;
+            if b != 0 {
+                self.partial.push(b);
+                continue;
+            }
+            // Several 0 in a row, there is no frame in between.
+            if self.partial.is_empty() {
+                continue;
+            }
+            let bytes = std::mem::take(&mut self.partial);
+            records.push(Record {
+                index: self.frames,
+                offset: self.offset - bytes.len() - 1,
+                at,
+                decoded: self.wire.decode(&bytes),
+                bytes,
+            });
+            self.frames += 1;
+        }
+        records
+    }
+
+    /// The bytes after the last delimiter, that never made a frame.
+    pub fn leftover(&self) -> &[u8] {
+        &self.partial
+    }
+}
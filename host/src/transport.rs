@@ -0,0 +1,90 @@
+//! `FrameTransport`s for the host: a loopback in memory, and a serial port or a pty.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use appcore::transport::SerialTransport;
+use embedded_hal::serial;
+use serialport::TTYPort;
+
+/// One direction of a loopback: what a side writes, the other reads.
+#[derive(Clone, Default)]
+pub struct Wire(Arc<Mutex<VecDeque<u8>>>);
+
+impl serial::Write<u8> for Wire {
+    type Error = Infallible;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Infallible> {
+        self.0.lock().unwrap().push_back(word);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+impl serial::Read<u8> for Wire {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Infallible> {
+        self.0
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(nb::Error::WouldBlock)
+    }
+}
+
+pub type Loopback = SerialTransport<Wire, Wire>;
+
+/// Two transports wired to each other, like the two boards with a null modem cable.
+pub fn loopback() -> (Loopback, Loopback) {
+    let (a_to_b, b_to_a) = (Wire::default(), Wire::default());
+    (
+        SerialTransport::new(a_to_b.clone(), b_to_a.clone()),
+        SerialTransport::new(b_to_a, a_to_b),
+    )
+}
+
+/// A serial port (or a pty) seen by embedded-hal, one byte at a time. A read that
+/// times out would block.
+pub struct Port(pub TTYPort);
+
+impl serial::Write<u8> for Port {
+    type Error = io::Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), io::Error> {
+        self.0.write_all(&[word]).map_err(nb::Error::Other)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), io::Error> {
+        self.0.flush().map_err(nb::Error::Other)
+    }
+}
+
+impl serial::Read<u8> for Port {
+    type Error = io::Error;
+
+    fn read(&mut self) -> nb::Result<u8, io::Error> {
+        let mut byte = [0];
+        match self.0.read(&mut byte) {
+            Ok(1) => Ok(byte[0]),
+            Ok(_) => Err(nb::Error::WouldBlock),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => Err(nb::Error::WouldBlock),
+            Err(e) => Err(nb::Error::Other(e)),
+        }
+    }
+}
+
+pub type Pty = SerialTransport<Port, Port>;
+
+/// Frames over `port`, see `open` and `TTYPort::pair`.
+pub fn pty(port: TTYPort) -> serialport::Result<Pty> {
+    let rx = port.try_clone_native()?;
+    Ok(SerialTransport::new(Port(port), Port(rx)))
+}
@@ -0,0 +1,141 @@
+//! Both sides of `interval_08`, written once against `Board`, run on the mock.
+
+use appcore::{
+    blinker::Blinker,
+    board::{is_pressed, read_gestures, show, write_frame, Board},
+    gesture::{Button, Gesture, Timings},
+    remote::{Remote, FINE_STEP},
+};
+use embedded_hal::{digital::v2::OutputPin, serial::Read, PwmPin};
+use host::{mock::MockBoard, to_frame, Splitter};
+use protocol::{
+    accumulator::{Accumulator, Feed},
+    frame, Command, Duration, Instant,
+};
+use rtic_monotonic::Monotonic;
+
+/// The nRF52: a press of the up or down button changes the interval. Called every
+/// millisecond, a change of `levels` is the edge of the GPIOTE interrupt.
+fn press_interval<B: Board>(
+    board: &mut B,
+    buttons: &mut [Button; 2],
+    levels: &mut [bool; 2],
+    remote: &mut Remote,
+    now: Instant,
+) {
+    for (i, button) in buttons.iter_mut().enumerate() {
+        let level = is_pressed(&board.buttons()[i]);
+        if level != levels[i] {
+            levels[i] = level;
+            button.on_edge(now);
+        }
+    }
+    let [up, down] = read_gestures(board, buttons, now).map(|g| g.is_some_and(Gesture::is_press));
+    if up || down {
+        let (tx, _) = board.uart();
+        write_frame(tx, &remote.interval(up, down)).ok();
+    }
+}
+
+/// The Nucleo: applies what came on the UART, then blinks.
+fn receive_and_blink<B: Board>(
+    board: &mut B,
+    frames: &mut Accumulator,
+    blinker: &mut Blinker,
+    now: Instant,
+) {
+    let (_, rx) = board.uart();
+    while let Ok(byte) = rx.read() {
+        if let Feed::Frame(command) = frames.feed_at(byte, now) {
+            blinker.on_command(command, now);
+        }
+    }
+    show(board.pwm(), blinker.blink(now));
+}
+
+#[test]
+fn the_nrf52_sends_one_interval_per_press() {
+    let mut board = MockBoard::new(4, 0);
+    let mut buttons = [(); 2].map(|_| Button::new(Timings::DEFAULT));
+    let mut levels = [false; 2];
+    let mut remote = Remote::new(FINE_STEP);
+    let mut run = |board: &mut MockBoard, ms: u32| {
+        for _ in 0..ms {
+            let now = board.clock.now;
+            press_interval(board, &mut buttons, &mut levels, &mut remote, now);
+            board.clock.advance(Duration::millis(1));
+        }
+    };
+
+    // up, bouncing for 3 ms, held and released
+    for pressed in [true, false, true, false, true] {
+        board.buttons[0].pressed = pressed;
+        run(&mut board, 1);
+    }
+    run(&mut board, 50);
+    board.buttons[0].pressed = false;
+    run(&mut board, 100);
+    board.buttons[1].pressed = true;
+    run(&mut board, 50);
+
+    let mut splitter = Splitter::default();
+    let sent: Vec<Command> = splitter
+        .push(&board.tx.sent)
+        .iter_mut()
+        .map(|f| frame::decode(f).unwrap())
+        .collect();
+    assert_eq!(
+        sent,
+        [Command::blink(1250, 1250), Command::blink(1000, 1000)]
+    );
+}
+
+#[test]
+fn the_nucleo_shows_what_it_receives() {
+    let mut board = MockBoard::new(1, 2400);
+    let mut frames = Accumulator::new();
+    let mut blinker = Blinker::new(2400);
+
+    board.receive(&to_frame(&Command::Pwm(100)));
+    let now = board.clock.now;
+    receive_and_blink(&mut board, &mut frames, &mut blinker, now);
+    assert_eq!(board.pwm.duty, 0);
+    receive_and_blink(&mut board, &mut frames, &mut blinker, now);
+    assert_eq!(board.pwm.duty, 259);
+
+    // failsafe once the heartbeats stop
+    board.clock.advance(Duration::from_ticks(2_000_000));
+    let now = board.clock.now;
+    blinker.link.poll(now);
+    receive_and_blink(&mut board, &mut frames, &mut blinker, now);
+    receive_and_blink(&mut board, &mut frames, &mut blinker, now);
+    assert_eq!(board.pwm.duty, 107);
+}
+
+#[test]
+fn the_parts_behave() {
+    let mut board = MockBoard::new(2, 100);
+    board.led().set_high().unwrap();
+    assert!(board.led.on);
+    board.led().set_low().unwrap();
+    assert!(!board.led.on);
+
+    // a duty above the maximum is the maximum
+    show(
+        board.pwm(),
+        appcore::blinker::Blink {
+            duty: 800,
+            next_in: Duration::millis(1000),
+        },
+    );
+    assert_eq!(board.pwm.duty, 100);
+    board.pwm().disable();
+    assert!(!board.pwm.enabled);
+
+    let (_, rx) = board.uart();
+    assert!(rx.read().is_err());
+    board.buttons()[1].pressed = true;
+    assert!(!is_pressed(&board.buttons[0]));
+    assert!(is_pressed(&board.buttons[1]));
+    assert_eq!(MockBoard::new(0, 0).clock.now().ticks(), 0);
+}
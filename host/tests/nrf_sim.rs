@@ -0,0 +1,66 @@
+//! Presses the buttons of the virtual nRF52, the test plays the Nucleo on the pty.
+
+use std::{
+    io::{Read, Write},
+    process::{Command as Process, Stdio},
+    time::Duration,
+};
+
+use host::Splitter;
+use protocol::{frame, Command};
+use serialport::{SerialPort, TTYPort};
+
+#[test]
+fn sends_what_the_buttons_do() {
+    let (mut nucleo, nrf) = TTYPort::pair().unwrap();
+    nucleo.set_timeout(Duration::from_millis(50)).unwrap();
+
+    let mut sim = Process::new(env!("CARGO_BIN_EXE_nrf-sim"))
+        .args(["--device", &nrf.name().unwrap()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    sim.stdin
+        .take()
+        .unwrap()
+        .write_all(b"up up down # a comment\non on off\nwait 700\ndown down\n")
+        .unwrap();
+    let output = sim.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let mut splitter = Splitter::default();
+    let mut commands: Vec<Command> = Vec::new();
+    let mut buf = [0u8; 64];
+    while let Ok(n) = nucleo.read(&mut buf) {
+        for mut f in splitter.push(&buf[..n]) {
+            commands.push(frame::decode(&mut f).unwrap());
+        }
+    }
+    let heartbeats = commands
+        .iter()
+        .filter(|c| **c == Command::Heartbeat)
+        .count();
+    assert!(heartbeats >= 2, "{:?}", commands);
+    commands.retain(|c| *c != Command::Heartbeat);
+    assert_eq!(
+        commands,
+        [
+            Command::blink(1250, 1250),
+            Command::blink(1500, 1500),
+            Command::blink(1250, 1250),
+            Command::Pwm(8),
+            Command::Pwm(16),
+            Command::Pwm(8),
+            Command::blink(1000, 1000),
+            Command::blink(750, 750),
+        ]
+    );
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("> Blink { on_ms: 1250, off_ms: 1250 } [04 0a e2 04 01 03 e2 04 01 "),
+        "{}",
+        stdout
+    );
+}
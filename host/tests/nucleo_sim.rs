@@ -0,0 +1,48 @@
+//! Plays the nRF52 against the virtual Nucleo, and watches its led.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Command as Process, Stdio},
+    thread,
+    time::Duration,
+};
+
+use host::{open, to_frame, BAUD_RATE};
+use protocol::Command;
+
+#[test]
+fn the_led_follows_the_commands() {
+    let mut sim = Process::new(env!("CARGO_BIN_EXE_nucleo-sim"))
+        .args(["--for", "4"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = BufReader::new(sim.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    let path = first.trim().strip_prefix("virtual Nucleo on ").unwrap();
+
+    let mut nrf = open(path, BAUD_RATE, Duration::from_millis(50)).unwrap();
+    nrf.write_all(&to_frame(&Command::Pwm(100))).unwrap();
+    // a heartbeat a bit faster than the nRF52, so the link stays up until the end
+    for _ in 0..9 {
+        thread::sleep(Duration::from_millis(400));
+        nrf.write_all(&to_frame(&Command::Heartbeat)).unwrap();
+    }
+
+    let lines: Vec<String> = stdout.lines().map(Result::unwrap).collect();
+    assert!(sim.wait().unwrap().success());
+    let events: Vec<&str> = lines
+        .iter()
+        .map(|l| l.split_once("s ").unwrap().1)
+        .collect();
+    assert!(events.contains(&"< Pwm(100)"), "{:#?}", lines);
+    assert!(events.contains(&"link up"), "{:#?}", lines);
+    assert!(!events.contains(&"link down, failsafe"), "{:#?}", lines);
+    // the brightness for level 100, once a second
+    let on = events.iter().filter(|e| **e == "led 259/2400").count();
+    assert!(on >= 1, "{:#?}", lines);
+    assert!(events
+        .iter()
+        .all(|e| !e.starts_with("led ") || *e == "led 0/2400" || *e == "led 259/2400"));
+}
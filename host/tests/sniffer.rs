@@ -0,0 +1,165 @@
+//! Feeds the sniffer a capture with good and bad frames.
+
+use std::{env, fs, process::Command as Process};
+
+use host::{
+    sniff::{reason, Sniffer},
+    to_frame, Message, Wire,
+};
+use protocol::{
+    reliable::{Receiver, Reply, Sender},
+    Command, Duration, Error, Instant, Response,
+};
+use serde::Serialize;
+
+/// A `Command` from a newer firmware, with a variant this one does not know.
+#[derive(Serialize)]
+enum FutureCommand {
+    _On,
+    _Off,
+    _Pwm(u8),
+    _Interval(u8),
+    _Heartbeat,
+    _Fade,
+    _Play,
+    _Step,
+    _Morse,
+    _Stop,
+    _Blink,
+    _Channel,
+    _Rgb,
+    Strobe(u8),
+}
+
+/// Good frames, with all the kinds of bad frames in between.
+fn capture() -> Vec<u8> {
+    let mut capture = vec![0x42, 0x17]; // the tail of a frame we joined midway
+    capture.push(0);
+    capture.extend(to_frame(&Command::On));
+    capture.extend(to_frame(&FutureCommand::Strobe(3)));
+    capture.extend(to_frame(&(Command::Off, 7u8)));
+    let mut corrupted = to_frame(&Command::Pwm(200));
+    corrupted[2] ^= 0x10;
+    capture.extend(corrupted);
+    capture.extend([0x05, 0x01, 0]); // COBS says 4 more bytes, there is 1
+    capture.extend(to_frame(&Command::Interval(2)));
+    capture.extend([0x01, 0x02]); // capture stopped in the middle of a frame
+    capture
+}
+
+#[test]
+fn every_frame_gets_a_record() {
+    let mut sniffer = Sniffer::default();
+    let records = sniffer.push(&capture(), None);
+    let outcomes: Vec<_> = records.iter().map(|r| r.decoded.clone()).collect();
+    assert_eq!(outcomes[1], Ok(Message::Command(Command::On)));
+    assert_eq!(outcomes[6], Ok(Message::Command(Command::Interval(2))));
+    let reasons: Vec<_> = outcomes
+        .iter()
+        .filter_map(|o| o.as_ref().err().map(reason))
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            "COBS error",
+            "unknown variant",
+            "trailing bytes",
+            "bad CRC",
+            "COBS error"
+        ]
+    );
+    assert_eq!(records[1].offset, 3);
+    assert_eq!(sniffer.leftover(), [0x01, 0x02]);
+    assert_eq!(
+        reason(&Error::Postcard(postcard::Error::DeserializeUnexpectedEnd)),
+        "too short"
+    );
+}
+
+/// Both wires of `reliable_09`: the requests of the nRF52 and the replies of the Nucleo.
+fn reliable_capture() -> (Vec<u8>, Vec<u8>) {
+    let mut sender = Sender::new(Duration::millis(50), 3);
+    let mut receiver = Receiver::new();
+    let (mut requests, mut replies) = (Vec::new(), Vec::new());
+    let commands = [
+        Command::Pwm(0),
+        Command::Heartbeat,
+        Command::blink(250, 250),
+    ];
+    for (ms, command) in (0..).step_by(100).zip(commands) {
+        let request = sender.send(command, Instant::from_ticks(ms * 1000));
+        requests.extend(to_frame(&request));
+        replies.extend(to_frame(&receiver.receive(request).0));
+    }
+    (requests, replies)
+}
+
+#[test]
+fn decodes_the_frames_of_reliable_09() {
+    let (requests, replies) = reliable_capture();
+    let records = Sniffer::new(Wire::Requests).push(&requests, None);
+    assert_eq!(records.len(), 3);
+    assert!(records
+        .iter()
+        .all(|r| matches!(r.decoded, Ok(Message::Request(_)))));
+    assert!(
+        records[0]
+            .to_string()
+            .contains("Request { seq: 0, command: Pwm(0) }"),
+        "{}",
+        records[0]
+    );
+    let records = Sniffer::new(Wire::Answers).push(&replies, None);
+    assert_eq!(
+        records[2].decoded,
+        Ok(Message::Reply(Reply {
+            seq: 2,
+            response: Response::Ack
+        }))
+    );
+
+    // read as plain commands, the requests are bad frames
+    let plain = Sniffer::default().push(&requests, None);
+    assert_eq!(plain[0].decoded, Err(Error::TrailingBytes));
+}
+
+#[test]
+fn records_survive_split_reads() {
+    let capture = capture();
+    let mut whole = Sniffer::default();
+    let expected: Vec<String> = whole
+        .push(&capture, None)
+        .iter()
+        .map(|r| r.to_string())
+        .collect();
+
+    let mut split = Sniffer::default();
+    let mut records = Vec::new();
+    for chunk in capture.chunks(3) {
+        records.extend(split.push(chunk, None).iter().map(|r| r.to_string()));
+    }
+    assert_eq!(records, expected);
+}
+
+#[test]
+fn prints_a_capture_file() {
+    let path = env::temp_dir().join(format!("sniffer-{}.bin", std::process::id()));
+    fs::write(&path, capture()).unwrap();
+    let output = Process::new(env!("CARGO_BIN_EXE_sniffer"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 8, "{}", stdout);
+    assert!(
+        lines[1].ends_with("#1    @3      Command::On [01 03 f0 e1 00]"),
+        "{}",
+        lines[1]
+    );
+    assert!(lines[2].contains("BAD (unknown variant)"), "{}", lines[2]);
+    assert!(lines[7].starts_with("unterminated frame"), "{}", lines[7]);
+}
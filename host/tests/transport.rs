@@ -0,0 +1,67 @@
+//! The same `send_command` and `parse` over every transport.
+
+use std::time::{Duration, Instant};
+
+use appcore::{blinker::Blinker, transport::FrameTransport};
+use host::transport::{loopback, pty};
+use protocol::Command;
+use serialport::{SerialPort, TTYPort};
+
+/// What the nRF52 does with a command.
+fn send_command<T: FrameTransport>(link: &mut T, cmd: Command) {
+    link.send(&cmd).ok();
+}
+
+/// What the Nucleo does with what it received, until nothing is left.
+fn parse<T: FrameTransport>(link: &mut T, blinker: &mut Blinker, now: protocol::Instant) -> usize {
+    let mut applied = 0;
+    loop {
+        match link.receive(now) {
+            Ok(command) => {
+                blinker.on_command(command, now);
+                applied += 1;
+            }
+            Err(nb::Error::WouldBlock) => return applied,
+            Err(nb::Error::Other(_)) => {}
+        }
+    }
+}
+
+#[test]
+fn loopback_both_ways() {
+    let (mut nrf, mut nucleo) = loopback();
+    let mut blinker = Blinker::new(2400);
+    let now = protocol::Instant::from_ticks(0);
+
+    assert_eq!(parse(&mut nucleo, &mut blinker, now), 0);
+    send_command(&mut nrf, Command::Pwm(100));
+    send_command(&mut nrf, Command::Interval(4));
+    assert_eq!(parse(&mut nucleo, &mut blinker, now), 2);
+    assert_eq!(blinker.times_ms(), (4000, 4000));
+    assert_eq!(nucleo.stats().received, 2);
+
+    // and back, like the responses of `reliable_09`
+    send_command(&mut nucleo, Command::Heartbeat);
+    assert_eq!(nrf.receive(now), Ok(Command::Heartbeat));
+    assert_eq!(nrf.receive::<Command>(now), Err(nb::Error::WouldBlock));
+}
+
+#[test]
+fn over_a_pty() {
+    let (master, slave) = TTYPort::pair().unwrap();
+    let mut nrf = pty(master).unwrap();
+    let mut slave = slave;
+    slave.set_timeout(Duration::from_millis(10)).unwrap();
+    let mut nucleo = pty(slave).unwrap();
+    let mut blinker = Blinker::new(2400);
+
+    send_command(&mut nrf, Command::Pwm(100));
+    send_command(&mut nrf, Command::Interval(3));
+    let start = Instant::now();
+    let mut applied = 0;
+    while applied < 2 && start.elapsed() < Duration::from_secs(5) {
+        applied += parse(&mut nucleo, &mut blinker, host::mono_now(start));
+    }
+    assert_eq!(applied, 2);
+    assert_eq!(blinker.times_ms(), (3000, 3000));
+}
@@ -0,0 +1,220 @@
+//! Runs `uartctl` against a pseudo-terminal, the test plays the Nucleo on the other side.
+
+use std::{
+    io::{Read, Write},
+    process::Command as Process,
+    thread,
+    time::{Duration, Instant},
+};
+
+use host::{describe, to_frame, Splitter, Wire};
+use protocol::{
+    frame,
+    reliable::{Reply, Request},
+    ChannelCommand, Command, Easing, Pattern, Response,
+};
+use serialport::{SerialPort, TTYPort};
+
+/// Reads what the tool wrote, until `count` frames came.
+fn receive(nucleo: &mut TTYPort, count: usize) -> Vec<Command> {
+    let mut splitter = Splitter::default();
+    let mut commands = Vec::new();
+    let mut buf = [0u8; 64];
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while commands.len() < count && Instant::now() < deadline {
+        if let Ok(n) = nucleo.read(&mut buf) {
+            for mut f in splitter.push(&buf[..n]) {
+                commands.push(frame::decode(&mut f).unwrap());
+            }
+        }
+    }
+    commands
+}
+
+#[test]
+fn sends_every_command_and_prints_the_answer() {
+    let (mut nucleo, tool) = TTYPort::pair().unwrap();
+    nucleo.set_timeout(Duration::from_millis(50)).unwrap();
+    let path = tool.name().unwrap();
+
+    // The Nucleo answers from another thread, `output` waits for the tool.
+    let nucleo = thread::spawn(move || {
+        let commands = receive(&mut nucleo, 18);
+        nucleo.write_all(&to_frame(&Response::Ack)).unwrap();
+        // keep the pty open until the tool is done listening
+        thread::sleep(Duration::from_millis(1500));
+        commands
+    });
+    let output = Process::new(env!("CARGO_BIN_EXE_uartctl"))
+        .args([&path, "--listen", "1000"])
+        .args(["on", "off", "pwm", "128", "interval", "3", "heartbeat"])
+        .args(["fade", "0", "1500", "in-out"])
+        .args(["step", "0", "255", "300", "step", "1", "0", "700"])
+        .args(["play", "uploaded", "0", "morse", "sos", "2", "stop"])
+        .args([
+            "blink",
+            "0",
+            "5",
+            "blink-duty",
+            "2000",
+            "10",
+            "interval",
+            "0",
+        ])
+        .args(["channel", "1", "blink", "5", "500", "channel", "3", "stop"])
+        .args([
+            "channel", "2", "play", "strobe", "2", "rgb", "255", "0", "64",
+        ])
+        .output()
+        .unwrap();
+    let commands = nucleo.join().unwrap();
+    drop(tool);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        commands,
+        vec![
+            Command::On,
+            Command::Off,
+            Command::Pwm(128),
+            Command::Interval(3),
+            Command::Heartbeat,
+            Command::Fade {
+                target: 0,
+                duration_ms: 1500,
+                easing: Easing::EaseInOut
+            },
+            Command::Step {
+                index: 0,
+                level: 255,
+                duration_ms: 300
+            },
+            Command::Step {
+                index: 1,
+                level: 0,
+                duration_ms: 700
+            },
+            Command::Play {
+                pattern: Pattern::Uploaded,
+                repeat: 0
+            },
+            Command::morse("sos", 2),
+            Command::Stop,
+            Command::Blink {
+                on_ms: 0,
+                off_ms: 10
+            },
+            Command::Blink {
+                on_ms: 200,
+                off_ms: 1800
+            },
+            Command::Interval(0),
+            Command::Channel {
+                channel: 1,
+                command: ChannelCommand::Blink {
+                    on_ms: 10,
+                    off_ms: 500
+                }
+            },
+            Command::Channel {
+                channel: 3,
+                command: ChannelCommand::Stop
+            },
+            Command::Channel {
+                channel: 2,
+                command: ChannelCommand::Play {
+                    pattern: Pattern::Strobe,
+                    repeat: 2
+                }
+            },
+            Command::Rgb {
+                red: 255,
+                green: 0,
+                blue: 64
+            }
+        ]
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("> Pwm(128) [05 02 80 e5 ea 00]"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("< Response::Ack"), "{}", stdout);
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    let run = |args: &[&str]| {
+        Process::new(env!("CARGO_BIN_EXE_uartctl"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    assert_eq!(run(&[]).status.code(), Some(2));
+    assert_eq!(
+        run(&["--wire", "both", "/dev/null", "on"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(run(&["/dev/null", "pwm", "256"]).status.code(), Some(2));
+    assert_eq!(run(&["/dev/null", "dance"]).status.code(), Some(2));
+    assert_eq!(
+        run(&["/dev/null", "fade", "255", "500", "bounce"])
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(run(&["/dev/null", "fade", "255"]).status.code(), Some(2));
+    assert_eq!(
+        run(&["/dev/null", "play", "disco", "0"]).status.code(),
+        Some(2)
+    );
+    assert_eq!(
+        run(&["/dev/null", "morse", "too long a text", "1"])
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(
+        run(&["/dev/null", "channel", "1", "morse", "sos", "1"])
+            .status
+            .code(),
+        Some(2)
+    );
+    assert_eq!(run(&["/dev/null", "channel", "1"]).status.code(), Some(2));
+    assert_eq!(run(&["/dev/null", "rgb", "1", "2"]).status.code(), Some(2));
+    assert_eq!(run(&["/dev/null"]).status.code(), Some(2));
+}
+
+/// The frame of `msg`, without its terminating 0, as `read_frames` returns it.
+fn unterminated<T: serde::Serialize>(msg: &T) -> Vec<u8> {
+    let mut frame = to_frame(msg);
+    frame.pop();
+    frame
+}
+
+#[test]
+fn the_wire_tells_what_a_frame_holds() {
+    // the same bytes on both sides
+    let pwm = unterminated(&Command::Pwm(0));
+    let reply = Reply {
+        seq: 2,
+        response: Response::Ack,
+    };
+    assert_eq!(pwm, unterminated(&reply));
+    assert_eq!(describe(&pwm, Wire::Commands), "Command::Pwm(0)");
+    assert_eq!(describe(&pwm, Wire::Answers), format!("{:?}", reply));
+
+    assert_eq!(
+        describe(&unterminated(&Response::Nack), Wire::Answers),
+        "Response::Nack"
+    );
+    let request = Request {
+        seq: 7,
+        command: Command::Pwm(0),
+    };
+    assert_eq!(
+        describe(&unterminated(&request), Wire::Requests),
+        format!("{:?}", request)
+    );
+}
@@ -7,13 +7,17 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[UARTE1])]
 mod app {
 
+    use appcore::sleep::SleepMeter;
     use nrf52840_hal::{
         gpio::{p0::Parts, Level, Output, Pin, PushPull},
         pac::TIMER2,
         prelude::{OutputPin, StatefulOutputPin},
     };
 
-    use nrfie::mono::{ExtU32, MonoTimer};
+    use nrfie::{
+        mono::{ExtU32, MonoTimer},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
 
     // Monotonic is a timer that never stops with a fixed tick rate.
     // Its type is "read only". RTIC is agnostic and has a trait called
@@ -53,11 +57,11 @@ mod app {
     }
 
     // Optional idle, can be removed if not needed.
-    #[idle]
-    fn idle(_: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         defmt::info!("idle");
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
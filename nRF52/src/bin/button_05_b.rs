@@ -6,20 +6,21 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use nrf52840_hal::prelude::OutputPin;
-    use nrf52840_hal::prelude::_embedded_hal_serial_Read;
-    use nrf52840_hal::uarte::UarteRx;
+    use appcore::sleep::SleepMeter;
+    use cortex_m::peripheral::NVIC;
     use nrf52840_hal::{
-        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Level, Output, Pin, PushPull},
-        pac::{TIMER2, UARTE1},
-        uarte::{Baudrate, Parity, Pins as UartePins, Uarte},
+        gpio::{Output, Pin, PushPull},
+        pac::Interrupt,
+        prelude::{_embedded_hal_serial_Read, OutputPin},
+    };
+    use nrfie::{
+        board::{Dk, Mono},
+        dma::{DmaRx, RX_CHUNK},
+        power::{wait_for_event, wake_on_pending, SLEEP_REPORT_PERIOD},
     };
-    use nrfie::mono::MonoTimer;
 
     #[monotonic(binds = TIMER2, default = true)]
-    type RticMono = MonoTimer<TIMER2>;
+    type RticMono = Mono;
 
     #[shared]
     struct Shared {}
@@ -27,51 +28,44 @@ mod app {
     #[local]
     struct Local {
         led: Pin<Output<PushPull>>,
-        rx: UarteRx<UARTE1>,
-        buf: Vec<u8, 3>,
+        rx: DmaRx,
     }
 
     // Buffers are static when initiated there
     #[init(local=[
         uart_tx_buff: [u8; 4] = [0;4],
-        uart_rx_buff: [u8; 1] = [0;1]
-        ]
-    )]
+        uart_rx_buff: [u8; 1] = [0;1],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
+    ])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        let timer = device.TIMER2;
-        let mono = RticMono::new(timer);
-        let buf = Vec::new();
-        let p0 = P0Parts::new(device.P0);
-        let p1 = P1Parts::new(device.P1);
-
-        // We need it for initialisation
-        let txd = p1
-            .p1_08
-            .into_push_pull_output(nrf52840_hal::gpio::Level::High)
-            .degrade();
-
-        let rxd = p1.p1_07.into_floating_input().degrade();
-        let pins = UartePins {
-            rxd,
-            txd,
-            cts: None,
-            rts: None,
-        };
-        let led = p0.p0_13.into_push_pull_output(Level::High).degrade();
-
-        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
-        let (_tx, rx) = uarte
-            .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
-            .unwrap();
+        let mut core = cx.core;
+        // Pins, UART and timer, see `nrfie::board`
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
+        // UARTE1 has no task: the end of a transfer only pends it, and that wakes idle
+        wake_on_pending(&mut core.SCB);
 
-        (Shared {}, Local { buf, led, rx }, init::Monotonics(mono))
+        (
+            Shared {},
+            Local {
+                led: board.led,
+                rx: board.rx,
+            },
+            init::Monotonics(mono),
+        )
     }
 
-    #[idle(local=[rx, led, buf])]
+    #[idle(local=[rx, led, meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
     fn idle(cx: idle::Context) -> ! {
         loop {
+            // Unpended before reading, a transfer ending after that still wakes us
+            NVIC::unpend(Interrupt::UARTE1);
+            cx.local.rx.on_interrupt();
             while let Ok(d) = cx.local.rx.read() {
                 defmt::info!("Received byte {:?}", d);
 
@@ -89,6 +83,7 @@ mod app {
                     }
                 }
             }
+            wait_for_event(cx.local.meter, monotonics::now);
         }
     }
 }
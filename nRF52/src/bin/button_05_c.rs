@@ -6,22 +6,28 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use nrf52840_hal::prelude::OutputPin;
-    use nrf52840_hal::prelude::_embedded_hal_serial_Read;
-    use nrf52840_hal::uarte::UarteRx;
+    use appcore::sleep::SleepMeter;
+    use cortex_m::peripheral::NVIC;
     use nrf52840_hal::{
-        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Level, Output, Pin, PushPull},
-        pac::{TIMER2, UARTE1},
-        uarte::{Baudrate, Parity, Pins as UartePins, Uarte},
+        gpio::{Output, Pin, PushPull},
+        pac::Interrupt,
+        prelude::{_embedded_hal_serial_Read, OutputPin},
+    };
+    use nrfie::{
+        board::{Dk, Mono},
+        dma::{DmaRx, RX_CHUNK},
+        power::{wait_for_event, wake_on_pending, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{inter_byte_timeout, Accumulator, Feed},
+        Command, Duration,
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
-    use postcard::from_bytes_cobs;
-    use serde::{Deserialize, Serialize};
+
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
 
     #[monotonic(binds = TIMER2, default = true)]
-    type RticMono = MonoTimer<TIMER2>;
+    type RticMono = Mono;
 
     #[shared]
     struct Shared {}
@@ -29,61 +35,50 @@ mod app {
     #[local]
     struct Local {
         led: Pin<Output<PushPull>>,
-        rx: UarteRx<UARTE1>,
-        buf: Vec<u8, 3>,
-    }
-    // This is the Command that will be sent instead of 0 or 1
-    #[derive(Serialize, Format, Deserialize, Clone, Copy)]
-    pub enum Command {
-        On,
-        Off,
+        rx: DmaRx,
+        frames: Accumulator,
     }
+
     // Buffers are static when initiated there
     #[init(local=[
         uart_tx_buff: [u8; 4] = [0;4],
-        uart_rx_buff: [u8; 1] = [0;1]
-        ]
-    )]
+        uart_rx_buff: [u8; 1] = [0;1],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
+    ])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        let timer = device.TIMER2;
-        let mono = RticMono::new(timer);
-        let buf = Vec::new();
-        let p0 = P0Parts::new(device.P0);
-        let p1 = P1Parts::new(device.P1);
-
-        // We need it for initialisation
-        let txd = p1
-            .p1_08
-            .into_push_pull_output(nrf52840_hal::gpio::Level::High)
-            .degrade();
-
-        let rxd = p1.p1_07.into_floating_input().degrade();
-        let pins = UartePins {
-            rxd,
-            txd,
-            cts: None,
-            rts: None,
-        };
-        let mut led = p0.p0_13.into_push_pull_output(Level::High).degrade();
-
-        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
-        let (_tx, rx) = uarte
-            .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
-            .unwrap();
+        let mut core = cx.core;
+        // Pins, UART and timer, see `nrfie::board`
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
+        // UARTE1 has no task: the end of a transfer only pends it, and that wakes idle
+        wake_on_pending(&mut core.SCB);
 
-        (Shared {}, Local { buf, led, rx }, init::Monotonics(mono))
+        (
+            Shared {},
+            Local {
+                frames: Accumulator::new().with_timeout(INTER_BYTE_TIMEOUT),
+                led: board.led,
+                rx: board.rx,
+            },
+            init::Monotonics(mono),
+        )
     }
 
-    #[idle(local=[rx, led, buf])]
+    #[idle(local=[rx, led, frames, meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
     fn idle(cx: idle::Context) -> ! {
         loop {
+            // Unpended before reading, a transfer ending after that still wakes us
+            NVIC::unpend(Interrupt::UARTE1);
+            cx.local.rx.on_interrupt();
             while let Ok(d) = cx.local.rx.read() {
                 defmt::info!("Received byte {:?}", d);
-                let _ = cx.local.buf.push(d);
-                if d == 0 {
-                    if let Ok(command) = from_bytes_cobs(cx.local.buf) {
+                match cx.local.frames.feed_at(d, monotonics::now()) {
+                    Feed::Frame(command) => {
                         defmt::debug!("Received {:?} 🟢 ", command);
                         match command {
                             Command::On => {
@@ -94,11 +89,19 @@ mod app {
                                 let _ = cx.local.led.set_high();
                                 defmt::debug!("Led sets high");
                             }
+                            // The Nucleo has only one button, it only sends On and Off.
+                            _ => {}
                         }
                     }
-                    cx.local.buf.clear();
+                    Feed::Consumed => {}
+                    dropped => defmt::warn!(
+                        "Dropped a frame ({:?}), {} so far.",
+                        dropped,
+                        cx.local.frames.stats.dropped()
+                    ),
                 }
             }
+            wait_for_event(cx.local.meter, monotonics::now);
         }
     }
 }
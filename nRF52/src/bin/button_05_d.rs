@@ -6,22 +6,33 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
-    use defmt::Format;
-    use heapless::Vec;
-    use nrf52840_hal::prelude::OutputPin;
-    use nrf52840_hal::prelude::_embedded_hal_serial_Read;
-    use nrf52840_hal::uarte::UarteRx;
+    use appcore::{
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport, Unconnected},
+    };
     use nrf52840_hal::{
-        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Level, Output, Pin, PushPull},
-        pac::{TIMER2, UARTE1},
-        uarte::{Baudrate, Parity, Pins as UartePins, Uarte},
+        gpio::{Output, Pin, PushPull},
+        prelude::OutputPin,
+        uarte::Error,
+    };
+    use nrfie::{
+        board::{Dk, Mono},
+        dma::{DmaRx, RX_CHUNK},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        Command, Duration,
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
-    use postcard::from_bytes_cobs;
-    use serde::{Deserialize, Serialize};
+
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
+
+    /// Only receives, the nRF52 has nothing to say.
+    type Receiver = SerialTransport<Unconnected<Error>, DmaRx>;
 
     #[monotonic(binds = TIMER2, default = true)]
-    type RticMono = MonoTimer<TIMER2>;
+    type RticMono = Mono;
 
     #[shared]
     struct Shared {}
@@ -29,68 +40,75 @@ mod app {
     #[local]
     struct Local {
         led: Pin<Output<PushPull>>,
-        uarte: Uarte<UARTE1>,
-        buf: Vec<u8, 3>,
-    }
-    // This is the Command that will be sent instead of 0 or 1
-    #[derive(Serialize, Format, Deserialize, Clone, Copy)]
-    pub enum Command {
-        On,
-        Off,
+        link: Receiver,
     }
+
     // Buffers are static when initiated there
     #[init(local=[
         uart_tx_buff: [u8; 4] = [0;4],
-        uart_rx_buff: [u8; 1] = [0;1]
-        ]
-    )]
+        uart_rx_buff: [u8; 1] = [0;1],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
+    ])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        let timer = device.TIMER2;
-        let mono = RticMono::new(timer);
-        let buf = Vec::new();
-        let p0 = P0Parts::new(device.P0);
-        let p1 = P1Parts::new(device.P1);
-
-        // We need it for initialisation
-        let txd = p1
-            .p1_08
-            .into_push_pull_output(nrf52840_hal::gpio::Level::High)
-            .degrade();
-
-        let rxd = p1.p1_07.into_floating_input().degrade();
-        let pins = UartePins {
-            rxd,
-            txd,
-            cts: None,
-            rts: None,
-        };
-        let mut led = p0.p0_13.into_push_pull_output(Level::High).degrade();
-
-        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
+        // Pins, UART and timer, see `nrfie::board`
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
 
-        (Shared {}, Local { buf, led, uarte }, init::Monotonics(mono))
+        (
+            Shared {},
+            Local {
+                led: board.led,
+                link: SerialTransport::receiver(board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+            },
+            init::Monotonics(mono),
+        )
     }
 
-    #[idle(local=[uarte, led])]
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
     fn idle(cx: idle::Context) -> ! {
         loop {
-            let mut buf = [0_u8; 3];
-            if cx.local.uarte.read(&mut buf).is_ok() {
-                if let Ok(command) = from_bytes_cobs(&mut buf) {
-                    match command {
-                        Command::On => {
-                            let _ = cx.local.led.set_high();
-                            defmt::debug!("We're high.");
-                        }
-                        Command::Off => {
-                            let _ = cx.local.led.set_low();
-                            defmt::debug!("We're low.");
-                        }
-                    }
+            sleep(cx.local.meter, monotonics::now);
+        }
+    }
+
+    /// The commands of the Nucleo, once an EasyDMA transfer ended, whatever their length.
+    #[task(binds=UARTE1, priority = 2, local=[link])]
+    fn uarte(cx: uarte::Context) {
+        let link = cx.local.link;
+        link.rx().on_interrupt();
+        loop {
+            match link.receive::<Command>(monotonics::now()) {
+                Ok(command) => {
+                    switch::spawn(command).ok();
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped())
                 }
             }
         }
     }
+
+    #[task(capacity = 4, priority = 1, local=[led])]
+    fn switch(cx: switch::Context, command: Command) {
+        match command {
+            Command::On => {
+                let _ = cx.local.led.set_high();
+                defmt::debug!("We're high.");
+            }
+            Command::Off => {
+                let _ = cx.local.led.set_low();
+                defmt::debug!("We're low.");
+            }
+            // The Nucleo has only one button, it only sends On and Off.
+            _ => {}
+        }
+    }
 }
@@ -1,4 +1,5 @@
 //! The nRF52 is blinking the light of the nucleo, with intervals. The light can be dimmed.
+//! A heartbeat tells the Nucleo that the nRF52 is still there.
 
 #![no_main]
 #![no_std]
@@ -7,169 +8,184 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
-    use cortex_m::prelude::_embedded_hal_serial_Write as hal_write;
-    use defmt::Format;
+    use appcore::{
+        board::poll_buttons,
+        gesture::{next_deadline, Button, Gesture, Timings},
+        remote::{Remote, FINE_STEP},
+        sleep::SleepMeter,
+        transport::SerialTransport,
+        txqueue::{same_kind, Overflow, TxQueue},
+    };
     use nrf52840_hal::{
-        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Input, Pin, PullUp},
+        gpio::{Input, Pin, PullUp},
         gpiote::Gpiote,
-        pac::{TIMER2, UARTE1},
-        prelude::InputPin,
-        uarte::{Baudrate, Parity, Pins as UartePins, Uarte, UarteTx},
+        pac::Interrupt,
+    };
+    use nrfie::{
+        board::{Dk, Link, Mono},
+        dma::{listen_endtx, pump, RX_CHUNK},
+        power::{sleep, SLEEP_REPORT_PERIOD},
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
-    use postcard::to_slice_cobs;
-    use serde::{Deserialize, Serialize};
+    use protocol::{link::HEARTBEAT_PERIOD, Command, Duration};
 
     #[monotonic(binds = TIMER2, default = true)]
-    type RticMono = MonoTimer<TIMER2>;
-    #[derive(Serialize, Deserialize, Format, Clone, Copy)]
-    // u8 is the most logical value, sends a pwm value between 0-255
-    pub enum Command {
-        On,
-        Off,
-        Pwm(u8),
-        Interval(u8),
-    }
+    type RticMono = Mono;
+
+    /// Holding a button repeats it, after a long press.
+    const TIMINGS: Timings = Timings::DEFAULT.with_repeat(Duration::millis(200));
+
     #[shared]
-    struct Shared {}
+    struct Shared {
+        // Up, down, brighter and darker. Both button tasks run at the same priority.
+        #[lock_free]
+        buttons: [Button; 4],
+        // A newer command replaces a waiting one of the same kind
+        tx_queue: TxQueue<Command, 4>,
+    }
 
     #[local]
     struct Local {
-        tx: UarteTx<UARTE1>,
+        link: Link,
+        remote: Remote,
         gpiote: Gpiote,
-        btn_up: Pin<Input<PullUp>>,
-        btn_down: Pin<Input<PullUp>>,
-        bright_on: Pin<Input<PullUp>>,
-        bright_off: Pin<Input<PullUp>>,
-        pwm: u8,
-        sec: u8,
+        // Up, down, brighter and darker
+        pins: [Pin<Input<PullUp>>; 4],
     }
 
     // Buffers are static when initiated there
     #[init(local=[
         uart_rx_buff: [u8;1] = [0;1],
-        uart_tx_buff: [u8;16] = [0;16]
+        uart_tx_buff: [u8;16] = [0;16],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
     ])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        let timer = device.TIMER2;
-        let mono = RticMono::new(timer);
-        let p1 = P1Parts::new(device.P1);
-        let p0 = P0Parts::new(device.P0);
-        let btn_up = p0.p0_11.into_pullup_input().degrade();
-        let btn_down = p0.p0_12.into_pullup_input().degrade();
-        let bright_on = p0.p0_24.into_pullup_input().degrade();
-        let bright_off = p0.p0_25.into_pullup_input().degrade();
-        let pwm = 0;
-        let sec = 1;
-        let txd = p1
-            .p1_08
-            .into_push_pull_output(nrf52840_hal::gpio::Level::High)
-            .degrade();
-
-        let rxd = p1.p1_07.into_floating_input().degrade();
-        let pins = UartePins {
-            rxd,
-            txd,
-            cts: None,
-            rts: None,
-        };
-
-        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
-        let (tx, _rx) = uarte
-            .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
-            .unwrap();
-        let gpiote = Gpiote::new(device.GPIOTE);
+        // Pins, UART and timer, see `nrfie::board`
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
+        let pins = board.buttons;
+        let [btn_up, btn_down, bright_on, bright_off] = &pins;
+        let gpiote = board.gpiote;
         gpiote
             .channel0()
-            .input_pin(&btn_up)
+            .input_pin(btn_up)
             .hi_to_lo()
             .enable_interrupt();
         gpiote
             .channel1()
-            .input_pin(&btn_down)
+            .input_pin(btn_down)
             .hi_to_lo()
             .enable_interrupt();
 
         // Port or Channels can be used here
-        gpiote.port().input_pin(&bright_on).low();
-        gpiote.port().input_pin(&bright_off).low();
+        gpiote.port().input_pin(bright_on).low();
+        gpiote.port().input_pin(bright_off).low();
         // Enable interrupt for port event
         gpiote.port().enable_interrupt();
+        listen_endtx();
+        heartbeat::spawn().ok();
 
         (
-            Shared {},
+            Shared {
+                buttons: [(); 4].map(|_| Button::new(TIMINGS)),
+                tx_queue: TxQueue::new(Overflow::Coalesce).with_same_kind(same_kind),
+            },
             Local {
-                tx,
-                btn_up,
-                btn_down,
+                link: SerialTransport::new(board.tx, board.rx),
+                remote: Remote::new(FINE_STEP),
                 gpiote,
-                pwm,
-                bright_on,
-                bright_off,
-                sec,
+                pins,
             },
             init::Monotonics(mono),
         )
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
-        loop {}
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
+        loop {
+            sleep(cx.local.meter, monotonics::now);
+        }
     }
 
-    #[task(binds=GPIOTE, local=[gpiote])]
+    /// The channels are the interval buttons, the port either brightness button. The
+    /// buttons are read once they stopped bouncing, see `appcore::gesture`.
+    #[task(binds=GPIOTE, local=[gpiote], shared=[buttons])]
     fn on_gpiote(cx: on_gpiote::Context) {
         let gpiote = cx.local.gpiote;
-        if gpiote.channel0().is_event_triggered() || gpiote.channel1().is_event_triggered() {
-            blink_led::spawn_after(10.millis()).ok();
-        }
-        if gpiote.port().is_event_triggered() {
-            change_pwm::spawn_after(10.millis()).ok();
+        let buttons = cx.shared.buttons;
+        let scheduled = next_deadline(buttons).is_some();
+        let now = monotonics::now();
+        let port = gpiote.port().is_event_triggered();
+        let edges = [
+            gpiote.channel0().is_event_triggered(),
+            gpiote.channel1().is_event_triggered(),
+            port,
+            port,
+        ];
+        for (button, &edge) in buttons.iter_mut().zip(edges.iter()) {
+            if edge {
+                button.on_edge(now);
+            }
         }
-
         gpiote.reset_events();
+        if let (false, Some(at)) = (scheduled, next_deadline(buttons)) {
+            read_buttons::spawn_at(at).ok();
+        }
     }
 
-    /// This task is adding an interval between the blinking.
-    #[task(local=[btn_up, btn_down, sec])]
-    fn blink_led(cx: blink_led::Context) {
-        if cx.local.btn_up.is_low().unwrap() {
-            *cx.local.sec += 1;
+    /// Reads the buttons until they are released and settled. A press changes the
+    /// interval or the brightness, and so does holding the button, repeatedly.
+    #[task(local=[pins, remote], shared=[buttons])]
+    fn read_buttons(cx: read_buttons::Context) {
+        let buttons = cx.shared.buttons;
+        let gestures = poll_buttons(cx.local.pins, buttons, monotonics::now());
+        if let Some(at) = next_deadline(buttons) {
+            read_buttons::spawn_at(at).ok();
         }
-        if cx.local.btn_down.is_low().unwrap() {
-            *cx.local.sec -= 1;
-        }
-        let sec = *cx.local.sec;
 
-        send_command::spawn(Command::Interval(sec)).ok();
+        let pressed = gestures.map(|gesture| {
+            gesture.is_some_and(|gesture| gesture.is_press() || gesture == Gesture::Repeat)
+        });
+        let [up, down, on, off] = pressed;
+        let remote = cx.local.remote;
+        if up || down {
+            send_command::spawn(remote.interval(up, down)).ok();
+        }
+        if on || off {
+            let cmd = remote.brightness(on, off);
+            defmt::info!("pwm sent : {:?}", cmd);
+            send_command::spawn(cmd).ok();
+        }
     }
 
-    #[task(local=[ pwm, bright_on, bright_off])]
-    fn change_pwm(cx: change_pwm::Context) {
-        if cx.local.bright_on.is_low().unwrap() && *cx.local.pwm < 255 {
-            defmt::info!("pwm before saturating add sent : {:?}", *cx.local.pwm);
-            *cx.local.pwm = (*cx.local.pwm).saturating_add(8);
-            defmt::info!("pwm after saturating add sent : {:?}", *cx.local.pwm);
-        } else if cx.local.bright_off.is_low().unwrap() && *cx.local.pwm > 0 {
-            defmt::info!("pwm before saturating sub sent : {:?}", cx.local.pwm);
-            *cx.local.pwm = (*cx.local.pwm).saturating_sub(8);
-
-            defmt::info!("pwm after saturating sub sent : {:?}", *cx.local.pwm);
-        }
-        let cmd = Command::Pwm(*cx.local.pwm);
-        send_command::spawn(cmd).ok();
+    /// Tells the Nucleo we are still there, even when no button is pressed.
+    #[task]
+    fn heartbeat(_cx: heartbeat::Context) {
+        send_command::spawn(Command::Heartbeat).ok();
+        heartbeat::spawn_after(HEARTBEAT_PERIOD).ok();
     }
 
-    #[task(local=[tx])]
-    fn send_command(cx: send_command::Context, cmd: Command) {
-        let mut buf = [0u8; 16];
-        let data = to_slice_cobs(&cmd, &mut buf).unwrap();
+    /// Queues the command, the UARTE1 interrupt sends it when the previous one is out.
+    #[task(capacity = 4, shared=[tx_queue])]
+    fn send_command(mut cx: send_command::Context, cmd: Command) {
+        cx.shared.tx_queue.lock(|queue| {
+            if !queue.push(cmd) {
+                defmt::warn!("TX queue full, {:?}", queue.stats);
+            }
+        });
+        rtic::pend(Interrupt::UARTE1);
+    }
 
-        for b in data.iter() {
-            let _ = cx.local.tx.write(*b);
-        }
-        let _ = cx.local.tx.flush();
+    /// A frame went out, or one was queued. The Nucleo sends nothing, what it would
+    /// receive is left in the chunks.
+    #[task(binds=UARTE1, priority = 2, local=[link], shared=[tx_queue])]
+    fn uarte(mut cx: uarte::Context) {
+        let link = cx.local.link;
+        link.rx().on_interrupt();
+        cx.shared.tx_queue.lock(|queue| pump(link.tx(), queue));
     }
 }
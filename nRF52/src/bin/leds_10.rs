@@ -0,0 +1,214 @@
+//! The nRF52 receives the commands this time, from `uartctl` or the other board, and
+//! shows them on the four leds of the DK: LED1 to LED4 are the channels 0 to 3 of
+//! `Command::Channel`, and LED2 to LED4 the red, green and blue of `Command::Rgb`.
+//! Each led blinks, is dimmed and plays its patterns on its own, like the Nucleo led
+//! of `interval_08`.
+//! `uartctl` sends no heartbeat: the link is not supervised, LED1 only blinks the
+//! failsafe until the first command.
+
+#![no_main]
+#![no_std]
+
+use nrfie as _; // global logger + panicking-behavior + memory layout
+
+#[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
+mod app {
+    use appcore::{
+        blinker::Blinker,
+        channels::{for_channel, CHANNELS},
+        pattern::Sequencer,
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport, Unconnected},
+    };
+    use nrf52840_hal::{
+        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Level},
+        pac::{PWM0, TIMER2},
+        prelude::*,
+        pwm::{Channel, Pwm},
+        uarte::{Baudrate, Error, Parity, Pins as UartePins, Uarte},
+    };
+    use nrfie::{
+        board::RX_IDLE,
+        dma::{DmaRx, RX_CHUNK},
+        mono::MonoTimer,
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        Command, Duration, Instant,
+    };
+
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
+    /// The channel of PWM0 of every led, LED1 first.
+    const LEDS: [Channel; CHANNELS] = [Channel::C0, Channel::C1, Channel::C2, Channel::C3];
+
+    /// Only receives, nothing is answered.
+    type Receiver = SerialTransport<Unconnected<Error>, DmaRx>;
+
+    #[monotonic(binds = TIMER2, default = true)]
+    type RticMono = MonoTimer<TIMER2>;
+
+    #[shared]
+    struct Shared {
+        // brightness and interval of every led, see `appcore::blinker`
+        #[lock_free]
+        blinker: Blinker<CHANNELS>,
+        // the pattern playing instead of the blink, of every led, see `appcore::pattern`
+        #[lock_free]
+        sequencers: [Sequencer; CHANNELS],
+        // The next `beat` of every led, to play a new pattern right away
+        #[lock_free]
+        next_beat: [Option<beat::SpawnHandle>; CHANNELS],
+        // `blink` and `beat` run at the same priority.
+        #[lock_free]
+        leds: Pwm<PWM0>,
+    }
+
+    #[local]
+    struct Local {
+        link: Receiver,
+    }
+
+    // Buffers are static when initiated there
+    #[init(local=[
+        uart_rx_buff: [u8;1] = [0;1],
+        uart_tx_buff: [u8;16] = [0;16],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
+    ])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        defmt::info!("init");
+        let device = cx.device;
+        let mono = RticMono::new(device.TIMER2);
+        let p1 = P1Parts::new(device.P1);
+        let p0 = P0Parts::new(device.P0);
+
+        // LED1 to LED4 on p0.13 to p0.16, dimmed at 1 kHz
+        let leds = Pwm::new(device.PWM0);
+        let pins = [
+            p0.p0_13.into_push_pull_output(Level::High).degrade(),
+            p0.p0_14.into_push_pull_output(Level::High).degrade(),
+            p0.p0_15.into_push_pull_output(Level::High).degrade(),
+            p0.p0_16.into_push_pull_output(Level::High).degrade(),
+        ];
+        for (&channel, pin) in LEDS.iter().zip(pins) {
+            leds.set_output_pin(channel, pin);
+        }
+        leds.set_period(1000u32.hz());
+        leds.enable();
+        let max_duty = leds.max_duty();
+
+        let pins = UartePins {
+            rxd: p1.p1_07.into_floating_input().degrade(),
+            txd: p1.p1_08.into_push_pull_output(Level::High).degrade(),
+            cts: None,
+            rts: None,
+        };
+        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
+        let (_tx, rx) = uarte
+            .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
+            .unwrap();
+        // The commands come by EasyDMA, a transfer ends when the line is idle
+        let rx = DmaRx::new(rx, device.TIMER1, device.PPI, cx.local.rx_chunks, RX_IDLE);
+
+        for channel in 0..CHANNELS {
+            blink::spawn(channel).ok();
+        }
+        (
+            Shared {
+                blinker: Blinker::channels(max_duty),
+                sequencers: [(); CHANNELS].map(|_| Sequencer::new(max_duty)),
+                next_beat: Default::default(),
+                leds,
+            },
+            Local {
+                link: SerialTransport::receiver(rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+            },
+            init::Monotonics(mono),
+        )
+    }
+
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
+        loop {
+            sleep(cx.local.meter, monotonics::now);
+        }
+    }
+
+    /// Whole frames, to the parser, once an EasyDMA transfer ended.
+    #[task(binds=UARTE1, priority = 2, local=[link])]
+    fn uarte(cx: uarte::Context) {
+        let link = cx.local.link;
+        link.rx().on_interrupt();
+        loop {
+            let at = monotonics::now();
+            match link.receive::<Command>(at) {
+                Ok(command) => {
+                    if parse::spawn(command, at).is_err() {
+                        defmt::warn!("Too many commands, {:?} is dropped.", command);
+                    }
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a frame, {} so far.", link.stats().dropped())
+                }
+            }
+        }
+    }
+
+    /// Gives every led the part of the command that is for it.
+    #[task(capacity = 4, shared=[blinker, sequencers, next_beat])]
+    fn parse(cx: parse::Context, command: Command, at: Instant) {
+        defmt::debug!("Received complete command: {:?}.", command);
+        if cx.shared.blinker.on_command(command, at).is_some() {
+            defmt::info!("First command 🔗");
+        }
+        for (channel, sequencer) in cx.shared.sequencers.iter_mut().enumerate() {
+            let command = for_channel(command, channel);
+            if command.is_some_and(|command| sequencer.apply(command)) {
+                restart_beat(&mut cx.shared.next_beat[channel], channel);
+            }
+        }
+    }
+
+    /// Shows the first step of the new pattern of `channel` now, instead of at the end
+    /// of the step of the old one. Scheduled rather than spawned, for a handle: another
+    /// command before the beat runs must be able to cancel it too, or two beats would
+    /// step the same pattern.
+    fn restart_beat(next_beat: &mut Option<beat::SpawnHandle>, channel: usize) {
+        if let Some(handle) = next_beat.take() {
+            handle.cancel().ok();
+        }
+        *next_beat = beat::spawn_after(Duration::from_ticks(0), channel).ok();
+    }
+
+    /// Turns the led of `channel` on or off, and spawns itself again after the time
+    /// lit or dark, or after a step of its fade. While a pattern plays, the blink keeps
+    /// its pace in the dark.
+    #[task(capacity = 4, shared=[blinker, sequencers, leds])]
+    fn blink(cx: blink::Context, channel: usize) {
+        let blink = cx.shared.blinker.blink_channel(channel, monotonics::now());
+        if !cx.shared.sequencers[channel].is_playing() {
+            cx.shared.leds.set_duty_on(LEDS[channel], blink.duty);
+        }
+        blink::spawn_after(blink.next_in, channel).ok();
+    }
+
+    /// Shows a step of the pattern of `channel` and spawns itself after it, until the
+    /// pattern is over. The blink shows again from then.
+    #[task(capacity = 4, shared=[sequencers, next_beat, leds])]
+    fn beat(cx: beat::Context, channel: usize) {
+        cx.shared.next_beat[channel] = match cx.shared.sequencers[channel].beat() {
+            Some(beat) => {
+                cx.shared.leds.set_duty_on(LEDS[channel], beat.duty);
+                beat::spawn_after(beat.next_in, channel).ok()
+            }
+            None => {
+                defmt::debug!("Pattern over on LED{}.", channel + 1);
+                None
+            }
+        };
+    }
+}
@@ -7,6 +7,7 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
 
+    use appcore::sleep::SleepMeter;
     use cortex_m::prelude::_embedded_hal_serial_Write as hal_write;
     use nrf52840_hal::gpio::PushPull;
     use nrf52840_hal::{
@@ -15,7 +16,10 @@ mod app {
         prelude::OutputPin,
         uarte::{Baudrate, Parity, Pins as UartePins, Uarte, UarteRx, UarteTx},
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
+    use nrfie::{
+        mono::{ExtU32, MonoTimer},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
 
     #[monotonic(binds = TIMER2, default = true)]
     type RticMono = MonoTimer<TIMER2>;
@@ -72,10 +76,10 @@ mod app {
         )
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
         loop {
-            continue;
+            sleep(cx.local.meter, monotonics::now);
         }
     }
 
@@ -26,8 +26,9 @@ mod app {
     fn idle(_: idle::Context) -> ! {
         defmt::info!("idle");
 
+        // No timer here to measure the sleep, see `power::sleep` in the next programs
         loop {
-            continue;
+            cortex_m::asm::wfi();
         }
     }
 
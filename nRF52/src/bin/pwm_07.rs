@@ -1,5 +1,4 @@
 //! The nRF52 is dimming the light of the Nucleo.
-//! ATM the dimmer function is bad, and need to be improved by making a function to dim, instead of magic numbers.
 #![no_main]
 #![no_std]
 
@@ -7,170 +6,143 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
-    use cortex_m::prelude::_embedded_hal_serial_Write as hal_write;
-    use defmt::Format;
-    use nrf52840_hal::{
-        gpio::{p0::Parts as P0Parts, p1::Parts as P1Parts, Input, Pin, PullUp},
-        gpiote::Gpiote,
-        pac::{TIMER2, UARTE1},
-        prelude::InputPin,
-        uarte::{Baudrate, Parity, Pins as UartePins, Uarte, UarteTx},
+    use appcore::{
+        board::{read_gestures, write_frame, Board},
+        gesture::{next_deadline, Button, Gesture, Timings},
+        remote::{switch, Remote, COARSE_STEP},
+        sleep::SleepMeter,
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
-    use postcard::to_slice_cobs;
-    use serde::{Deserialize, Serialize};
+    use nrfie::{
+        board::{Dk, Mono},
+        dma::RX_CHUNK,
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::Command;
 
     #[monotonic(binds = TIMER2, default = true)]
-    type RticMono = MonoTimer<TIMER2>;
+    type RticMono = Mono;
 
-    // This is the Command that will be sent instead of 0 or 1
-    // It has been augmented with a Pwm() with an inner level
-    // u8 is the most logical container, as we send magic numbers to ajust the power of the dimmer,
-    // and it is easier with a pwm value between 0-255
-    // But on the other side we sill use a u16 because .get_max_duty() of pwm returns a u16.
-    #[derive(Serialize, Deserialize, Format, Clone, Copy)]
-    pub enum Command {
-        On,
-        Off,
-        Pwm(u8),
-    }
     #[shared]
-    struct Shared {}
+    struct Shared {
+        // The buttons, their interrupt and the UART. All the tasks run at the same priority.
+        #[lock_free]
+        board: Dk,
+        // On, off, brighter and darker.
+        #[lock_free]
+        buttons: [Button; 4],
+    }
 
     #[local]
     struct Local {
-        tx: UarteTx<UARTE1>,
-        gpiote: Gpiote,
-        btn_on: Pin<Input<PullUp>>,
-        btn_off: Pin<Input<PullUp>>,
-        bright_on: Pin<Input<PullUp>>,
-        bright_off: Pin<Input<PullUp>>,
-        pwm: u8,
+        remote: Remote,
     }
 
     // Buffers are static when initiated there
     #[init(local=[
         uart_rx_buff: [u8;1] = [0;1],
-        uart_tx_buff: [u8;16] = [0;16]
+        uart_tx_buff: [u8;16] = [0;16],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
     ])]
     fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
-        let device = cx.device;
-        let timer = device.TIMER2;
-        let mono = RticMono::new(timer);
-        let p1 = P1Parts::new(device.P1);
-        let p0 = P0Parts::new(device.P0);
-        let btn_on = p0.p0_11.into_pullup_input().degrade();
-        let btn_off = p0.p0_12.into_pullup_input().degrade();
-        let bright_on = p0.p0_24.into_pullup_input().degrade();
-        let bright_off = p0.p0_25.into_pullup_input().degrade();
-        let pwm = 0;
-
-        let txd = p1
-            .p1_08
-            .into_push_pull_output(nrf52840_hal::gpio::Level::High)
-            .degrade();
-
-        let rxd = p1.p1_07.into_floating_input().degrade();
-        let pins = UartePins {
-            rxd,
-            txd,
-            cts: None,
-            rts: None,
-        };
-
-        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
-        let (tx, _rx) = uarte
-            .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
-            .unwrap();
-        let gpiote = Gpiote::new(device.GPIOTE);
+        // Pins, UART and timer, see `nrfie::board`
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
+        let [btn_on, btn_off, bright_on, bright_off] = &board.buttons;
+        let gpiote = &board.gpiote;
         gpiote
             .channel0()
-            .input_pin(&btn_on)
+            .input_pin(btn_on)
             .hi_to_lo()
             .enable_interrupt();
         gpiote
             .channel1()
-            .input_pin(&btn_off)
+            .input_pin(btn_off)
             .hi_to_lo()
             .enable_interrupt();
 
         // Port or Channels can be used here
-        gpiote.port().input_pin(&bright_on).low();
-        gpiote.port().input_pin(&bright_off).low();
+        gpiote.port().input_pin(bright_on).low();
+        gpiote.port().input_pin(bright_off).low();
         // Enable interrupt for port event
         gpiote.port().enable_interrupt();
 
         (
-            Shared {},
+            Shared {
+                board,
+                buttons: [(); 4].map(|_| Button::new(Timings::DEFAULT)),
+            },
             Local {
-                tx,
-                btn_on,
-                btn_off,
-                gpiote,
-                pwm,
-                bright_on,
-                bright_off,
+                remote: Remote::new(COARSE_STEP),
             },
             init::Monotonics(mono),
         )
     }
 
-    #[idle]
-    fn idle(_cx: idle::Context) -> ! {
-        loop {}
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
+        loop {
+            sleep(cx.local.meter, monotonics::now);
+        }
     }
 
     /// This task dispatch from port and channels.
-    /// If port is triggered we turn the light on or off -> call to task blink_led
-    /// If the channel0 or channel1 are triggered we use the dimmer! -> call to task change_pwm
-    #[task(binds=GPIOTE, local=[gpiote])]
+    /// The channels are the on and off buttons, the port either brightness button. The
+    /// buttons are read once they stopped bouncing, see `appcore::gesture`.
+    #[task(binds=GPIOTE, shared=[board, buttons])]
     fn on_gpiote(cx: on_gpiote::Context) {
-        let gpiote = cx.local.gpiote;
-        if gpiote.channel0().is_event_triggered() || gpiote.channel1().is_event_triggered() {
-            blink_led::spawn_after(15.millis()).ok();
+        let gpiote = &cx.shared.board.gpiote;
+        let buttons = cx.shared.buttons;
+        let scheduled = next_deadline(buttons).is_some();
+        let now = monotonics::now();
+        let port = gpiote.port().is_event_triggered();
+        let edges = [
+            gpiote.channel0().is_event_triggered(),
+            gpiote.channel1().is_event_triggered(),
+            port,
+            port,
+        ];
+        for (button, &edge) in buttons.iter_mut().zip(edges.iter()) {
+            if edge {
+                button.on_edge(now);
+            }
         }
-        if gpiote.port().is_event_triggered() {
-            change_pwm::spawn_after(15.millis()).ok();
-        }
-
         gpiote.reset_events();
-    }
-    #[task(local=[btn_on, btn_off])]
-    fn blink_led(cx: blink_led::Context) {
-        if cx.local.btn_on.is_low().unwrap() {
-            send_command::spawn(Command::On).ok();
-        } else if cx.local.btn_off.is_low().unwrap() {
-            send_command::spawn(Command::Off).ok();
+        if let (false, Some(at)) = (scheduled, next_deadline(buttons)) {
+            read_buttons::spawn_at(at).ok();
         }
     }
 
-    /// This task controls the dimmer. We use saturating adds and subs to keep values
-    /// between 0 and 255. We increment by 32 to have 8 levels of brightness.
-    #[task(local=[ pwm, bright_on, bright_off])]
-    fn change_pwm(cx: change_pwm::Context) {
-        if cx.local.bright_on.is_low().unwrap() && *cx.local.pwm < 255 {
-            defmt::info!("pwm before saturating add sent : {:?}", *cx.local.pwm);
-            *cx.local.pwm = (*cx.local.pwm).saturating_add(32);
-            defmt::info!("pwm after saturating add sent : {:?}", *cx.local.pwm);
-        } else if cx.local.bright_off.is_low().unwrap() && *cx.local.pwm > 0 {
-            defmt::info!("pwm before saturating sub sent : {:?}", cx.local.pwm);
-            *cx.local.pwm = (*cx.local.pwm).saturating_sub(32);
+    /// Reads the buttons until they are released and settled. A press turns the light
+    /// on or off, or controls the dimmer: the level stays between 0 and 255, and changes
+    /// by 32 to have 8 levels of brightness.
+    #[task(local=[remote], shared=[board, buttons])]
+    fn read_buttons(cx: read_buttons::Context) {
+        let buttons = cx.shared.buttons;
+        let gestures = read_gestures(cx.shared.board, buttons, monotonics::now());
+        if let Some(at) = next_deadline(buttons) {
+            read_buttons::spawn_at(at).ok();
+        }
 
-            defmt::info!("pwm after saturating sub sent : {:?}", *cx.local.pwm);
+        let [on, off, brighter, darker] =
+            gestures.map(|gesture| gesture.is_some_and(Gesture::is_press));
+        if let Some(cmd) = switch(on, off) {
+            send_command::spawn(cmd).ok();
+        }
+        if brighter || darker {
+            let cmd = cx.local.remote.brightness(brighter, darker);
+            defmt::info!("pwm sent : {:?}", cmd);
+            send_command::spawn(cmd).ok();
         }
-        let cmd = Command::Pwm(*cx.local.pwm);
-        send_command::spawn(cmd).ok();
     }
 
-    #[task(local=[tx])]
+    #[task(capacity = 2, shared=[board])]
     fn send_command(cx: send_command::Context, cmd: Command) {
-        let mut buf = [0u8; 16];
-        let data = to_slice_cobs(&cmd, &mut buf).unwrap();
-
-        for b in data.iter() {
-            let _ = cx.local.tx.write(*b);
-        }
-        let _ = cx.local.tx.flush();
+        let (tx, _) = cx.shared.board.uart();
+        write_frame(tx, &cmd).ok();
     }
 }
@@ -0,0 +1,250 @@
+//! Same as `interval_08`, but the Nucleo acknowledges every command.
+//! A command without an answer is sent again, a few times, before giving up.
+
+#![no_main]
+#![no_std]
+
+use nrfie as _; // global logger + panicking-behavior + memory layout
+
+#[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO, SWI0_EGU0])]
+mod app {
+    use appcore::{
+        board::poll_buttons,
+        gesture::{next_deadline, Button, Gesture, Timings},
+        remote::{Remote, FINE_STEP},
+        sleep::SleepMeter,
+        transport::{FrameTransport, SerialTransport, Unconnected},
+        txqueue::{Overflow, TxQueue},
+    };
+    use nrf52840_hal::{
+        gpio::{Input, Pin, PullUp},
+        gpiote::Gpiote,
+        pac::{Interrupt, UARTE1},
+        uarte::{Error, UarteTx},
+    };
+    use nrfie::{
+        board::{Dk, Mono},
+        dma::{listen_endtx, pump, DmaRx, RX_CHUNK},
+        power::{sleep, SLEEP_REPORT_PERIOD},
+    };
+    use protocol::{
+        accumulator::{char_time, inter_byte_timeout},
+        reliable::{Action, Reply, Request, Sender},
+        Command, Duration,
+    };
+
+    /// How long the Nucleo has to answer. A frame is ~7 bytes, that is ~7 ms at 9600 baud.
+    const TIMEOUT: Duration = Duration::from_ticks(50_000);
+    /// How many times a command is sent again before giving up.
+    const MAX_RETRIES: u8 = 3;
+    /// A partial frame is dropped when the line is silent for 10 characters (~10 ms).
+    const INTER_BYTE_TIMEOUT: Duration = inter_byte_timeout(9600, 10);
+    /// Holding a button repeats it, after a long press, like in `interval_08`.
+    const TIMINGS: Timings = Timings::DEFAULT.with_repeat(Duration::millis(200));
+
+    /// Only receives, `transmit` writes with the `UarteTx`.
+    type Receiver = SerialTransport<Unconnected<Error>, DmaRx>;
+
+    #[monotonic(binds = TIMER2, default = true)]
+    type RticMono = Mono;
+
+    #[shared]
+    struct Shared {
+        #[lock_free]
+        sender: Sender,
+        // Up, down, brighter and darker. Both button tasks run at the same priority.
+        #[lock_free]
+        buttons: [Button; 4],
+        // `transmit` and `uarte` run at the same priority. A new request makes the
+        // waiting ones useless, `Sender` forgot them.
+        #[lock_free]
+        tx_queue: TxQueue<Request, 2>,
+    }
+
+    #[local]
+    struct Local {
+        tx: UarteTx<UARTE1>,
+        link: Receiver,
+        remote: Remote,
+        gpiote: Gpiote,
+        // Up, down, brighter and darker
+        pins: [Pin<Input<PullUp>>; 4],
+    }
+
+    // Buffers are static when initiated there
+    #[init(local=[
+        uart_rx_buff: [u8;1] = [0;1],
+        uart_tx_buff: [u8;16] = [0;16],
+        rx_chunks: [[u8; RX_CHUNK]; 2] = [[0; RX_CHUNK]; 2]
+    ])]
+    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+        defmt::info!("init");
+        // Pins, UART and timer, see `nrfie::board`. The replies come by EasyDMA, a
+        // transfer ends when the line is idle.
+        let (board, mono) = Dk::new(
+            cx.device,
+            cx.local.uart_tx_buff,
+            cx.local.uart_rx_buff,
+            cx.local.rx_chunks,
+        );
+        let pins = board.buttons;
+        let [btn_up, btn_down, bright_on, bright_off] = &pins;
+        let gpiote = board.gpiote;
+        gpiote
+            .channel0()
+            .input_pin(btn_up)
+            .hi_to_lo()
+            .enable_interrupt();
+        gpiote
+            .channel1()
+            .input_pin(btn_down)
+            .hi_to_lo()
+            .enable_interrupt();
+
+        // Port or Channels can be used here
+        gpiote.port().input_pin(bright_on).low();
+        gpiote.port().input_pin(bright_off).low();
+        // Enable interrupt for port event
+        gpiote.port().enable_interrupt();
+        listen_endtx();
+
+        (
+            Shared {
+                sender: Sender::new(TIMEOUT, MAX_RETRIES),
+                buttons: [(); 4].map(|_| Button::new(TIMINGS)),
+                tx_queue: TxQueue::new(Overflow::DropOldest),
+            },
+            Local {
+                tx: board.tx,
+                link: SerialTransport::receiver(board.rx)
+                    .with_timeout(INTER_BYTE_TIMEOUT)
+                    .backdated(char_time(9600)),
+                remote: Remote::new(FINE_STEP),
+                gpiote,
+                pins,
+            },
+            init::Monotonics(mono),
+        )
+    }
+
+    #[idle(local=[meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
+    fn idle(cx: idle::Context) -> ! {
+        loop {
+            sleep(cx.local.meter, monotonics::now);
+        }
+    }
+
+    /// The replies of the Nucleo once an EasyDMA transfer ended, and the next request
+    /// once the previous one is out.
+    #[task(binds=UARTE1, priority = 2, local=[tx, link], shared=[tx_queue])]
+    fn uarte(cx: uarte::Context) {
+        pump(cx.local.tx, cx.shared.tx_queue);
+        let link = cx.local.link;
+        link.rx().on_interrupt();
+        loop {
+            match link.receive::<Reply>(monotonics::now()) {
+                Ok(reply) => {
+                    on_reply::spawn(reply).ok();
+                }
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(_)) => {
+                    defmt::warn!("Dropped a reply, {} so far.", link.stats().dropped())
+                }
+            }
+        }
+    }
+
+    /// The channels are the interval buttons, the port either brightness button. The
+    /// buttons are read once they stopped bouncing, see `appcore::gesture`.
+    #[task(binds=GPIOTE, local=[gpiote], shared=[buttons])]
+    fn on_gpiote(cx: on_gpiote::Context) {
+        let gpiote = cx.local.gpiote;
+        let buttons = cx.shared.buttons;
+        let scheduled = next_deadline(buttons).is_some();
+        let now = monotonics::now();
+        let port = gpiote.port().is_event_triggered();
+        let edges = [
+            gpiote.channel0().is_event_triggered(),
+            gpiote.channel1().is_event_triggered(),
+            port,
+            port,
+        ];
+        for (button, &edge) in buttons.iter_mut().zip(edges.iter()) {
+            if edge {
+                button.on_edge(now);
+            }
+        }
+        gpiote.reset_events();
+        if let (false, Some(at)) = (scheduled, next_deadline(buttons)) {
+            read_buttons::spawn_at(at).ok();
+        }
+    }
+
+    /// Reads the buttons until they are released and settled. A press changes the
+    /// interval or the brightness, and so does holding the button, repeatedly.
+    #[task(local=[pins, remote], shared=[buttons])]
+    fn read_buttons(cx: read_buttons::Context) {
+        let buttons = cx.shared.buttons;
+        let gestures = poll_buttons(cx.local.pins, buttons, monotonics::now());
+        if let Some(at) = next_deadline(buttons) {
+            read_buttons::spawn_at(at).ok();
+        }
+
+        let pressed = gestures.map(|gesture| {
+            gesture.is_some_and(|gesture| gesture.is_press() || gesture == Gesture::Repeat)
+        });
+        let [up, down, on, off] = pressed;
+        let remote = cx.local.remote;
+        if up || down {
+            send_command::spawn(remote.interval(up, down)).ok();
+        }
+        if on || off {
+            send_command::spawn(remote.brightness(on, off)).ok();
+        }
+    }
+
+    /// Numbers the command and sends it. A command still waiting for its
+    /// acknowledgement is forgotten, the newest one is what the led should show.
+    #[task(capacity = 2, shared=[sender])]
+    fn send_command(cx: send_command::Context, cmd: Command) {
+        let request = cx.shared.sender.send(cmd, monotonics::now());
+        transmit::spawn(request).ok();
+    }
+
+    #[task(shared=[sender])]
+    fn on_reply(cx: on_reply::Context, reply: Reply) {
+        defmt::debug!("Received {:?}", reply);
+        if let Some(action) = cx.shared.sender.on_reply(reply, monotonics::now()) {
+            act(action);
+        }
+    }
+
+    #[task(capacity = 4, shared=[sender])]
+    fn check_timeout(cx: check_timeout::Context) {
+        // None when acknowledged, or when a newer request was sent in the meantime:
+        // `transmit` spawned its own check.
+        if let Some(action) = cx.shared.sender.poll(monotonics::now()) {
+            act(action);
+        }
+    }
+
+    fn act(action: Action) {
+        match action {
+            Action::Transmit(request) => {
+                defmt::info!("No ack for {:?}, sending it again", request);
+                transmit::spawn(request).ok();
+            }
+            Action::GiveUp(request) => defmt::warn!("Gave up on {:?}", request),
+        }
+    }
+
+    /// Queues the request for the wire, and checks after `TIMEOUT` that it was acknowledged.
+    #[task(capacity = 4, priority = 2, shared=[tx_queue])]
+    fn transmit(cx: transmit::Context, request: Request) {
+        if !cx.shared.tx_queue.push(request) {
+            defmt::warn!("TX queue full, {:?}", cx.shared.tx_queue.stats);
+        }
+        rtic::pend(Interrupt::UARTE1);
+        check_timeout::spawn_after(TIMEOUT).ok();
+    }
+}
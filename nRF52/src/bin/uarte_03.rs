@@ -6,14 +6,20 @@ use nrfie as _; // global logger + panicking-behavior + memory layout
 
 #[rtic::app(device = nrf52840_hal::pac, dispatchers=[RADIO])]
 mod app {
+    use appcore::sleep::SleepMeter;
+    use cortex_m::peripheral::NVIC;
     use nrf52840_hal::prelude::_embedded_hal_blocking_serial_Write;
     use nrf52840_hal::prelude::_embedded_hal_serial_Read;
     use nrf52840_hal::{
         gpio::p1::Parts,
-        pac::{TIMER2, UARTE1},
+        pac::{Interrupt, TIMER2, UARTE1},
         uarte::{Baudrate, Parity, Pins as UartePins, Uarte, UarteRx, UarteTx},
     };
-    use nrfie::mono::{ExtU32, MonoTimer};
+    use nrfie::{
+        dma::listen_endrx,
+        mono::{ExtU32, MonoTimer},
+        power::{wait_for_event, wake_on_pending, SLEEP_REPORT_PERIOD},
+    };
 
     #[monotonic(binds = TIMER2, default = true)]
     type RticMono = MonoTimer<TIMER2>;
@@ -35,7 +41,7 @@ mod app {
         uart_rx_buff: [u8; 1] = [0;1]
         ]
     )]
-    fn init(cx: init::Context) -> (Shared, Local, init::Monotonics) {
+    fn init(mut cx: init::Context) -> (Shared, Local, init::Monotonics) {
         defmt::info!("init");
         let device = cx.device;
         let timer = device.TIMER2;
@@ -60,6 +66,9 @@ mod app {
         let (tx, rx) = uarte
             .split(cx.local.uart_tx_buff, cx.local.uart_rx_buff)
             .unwrap();
+        // UARTE1 has no task: a byte only pends it, and that wakes idle
+        listen_endrx();
+        wake_on_pending(&mut cx.core.SCB);
         sending_buffer::spawn().ok();
 
         (
@@ -77,10 +86,12 @@ mod app {
     /// This function receives 4 u8.
     /// It needs to be done in an idle task because the idle task is always on.
     /// It will then convert the 4 u8 into a beer emoji with core::str::from_utf8() by passing the buffert.
-    #[idle(local=[rx, counter, buf])]
+    #[idle(local=[rx, counter, buf, meter: SleepMeter = SleepMeter::new(SLEEP_REPORT_PERIOD)])]
     fn idle(cx: idle::Context) -> ! {
         loop {
-            if let Ok(byte) = cx.local.rx.read() {
+            // Unpended before reading, a byte arriving after that still wakes us
+            NVIC::unpend(Interrupt::UARTE1);
+            while let Ok(byte) = cx.local.rx.read() {
                 cx.local.buf[*cx.local.counter] = byte;
                 *cx.local.counter += 1;
                 if *cx.local.counter == 4 {
@@ -91,6 +102,7 @@ mod app {
                     }
                 }
             }
+            wait_for_event(cx.local.meter, monotonics::now);
         }
     }
 
@@ -0,0 +1,144 @@
+//! The nRF52840 DK, set up the way the examples use it.
+
+use appcore::{board::Board, transport::SerialTransport};
+use embedded_hal::PwmPin;
+use nrf52840_hal::{
+    gpio::{
+        p0::Parts as P0Parts, p1::Parts as P1Parts, Input, Level, Output, Pin, PullUp, PushPull,
+    },
+    gpiote::Gpiote,
+    pac::{self, PWM0, TIMER2, UARTE1},
+    prelude::*,
+    pwm::{Channel, Pwm},
+    uarte::{Baudrate, Parity, Pins as UartePins, Uarte, UarteTx},
+};
+use protocol::{accumulator::inter_byte_timeout, Duration};
+
+use crate::{
+    dma::{DmaRx, RX_CHUNK},
+    mono::MonoTimer,
+};
+
+pub type Mono = MonoTimer<TIMER2>;
+
+/// Frames over UARTE1, see `appcore::transport`.
+pub type Link = SerialTransport<UarteTx<UARTE1>, DmaRx>;
+
+/// An EasyDMA transfer ends after 2 silent characters, see `nrfie::dma`.
+pub const RX_IDLE: Duration = inter_byte_timeout(9600, 2);
+
+/// LED2, on channel 0 of PWM0. LED1 stays a plain pin.
+pub struct DimmedLed(pub Pwm<PWM0>);
+
+impl PwmPin for DimmedLed {
+    type Duty = u16;
+
+    fn disable(&mut self) {
+        self.0.disable_channel(Channel::C0);
+    }
+
+    fn enable(&mut self) {
+        self.0.enable_channel(Channel::C0);
+    }
+
+    fn get_duty(&self) -> u16 {
+        self.0.duty_on(Channel::C0)
+    }
+
+    fn get_max_duty(&self) -> u16 {
+        self.0.max_duty()
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.0.set_duty_on(Channel::C0, duty)
+    }
+}
+
+pub struct Dk {
+    /// LED1 on p0.13, off.
+    pub led: Pin<Output<PushPull>>,
+    /// Buttons 1 to 4 on p0.11, p0.12, p0.24 and p0.25.
+    pub buttons: [Pin<Input<PullUp>>; 4],
+    /// UARTE1 on p1.08 (TX) and p1.07 (RX), received by EasyDMA with TIMER1 and the PPI.
+    pub tx: UarteTx<UARTE1>,
+    pub rx: DmaRx,
+    pub pwm: DimmedLed,
+    /// Not configured: which button fires which event is up to the example.
+    pub gpiote: Gpiote,
+}
+
+impl Dk {
+    /// The UART at 9600 bauds, LED2 dimmed at 1 kHz. EasyDMA needs the UART buffers
+    /// in RAM for the whole program, hence the `'static`. `uart_rx_buff` is the one the
+    /// HAL wants, the bytes come in `rx_chunks`. The monotonic on TIMER2 comes apart,
+    /// it is RTIC's.
+    pub fn new(
+        device: pac::Peripherals,
+        uart_tx_buff: &'static mut [u8],
+        uart_rx_buff: &'static mut [u8; 1],
+        rx_chunks: &'static mut [[u8; RX_CHUNK]; 2],
+    ) -> (Self, Mono) {
+        let mono = Mono::new(device.TIMER2);
+        let p1 = P1Parts::new(device.P1);
+        let p0 = P0Parts::new(device.P0);
+        let buttons = [
+            p0.p0_11.into_pullup_input().degrade(),
+            p0.p0_12.into_pullup_input().degrade(),
+            p0.p0_24.into_pullup_input().degrade(),
+            p0.p0_25.into_pullup_input().degrade(),
+        ];
+        let led = p0.p0_13.into_push_pull_output(Level::High).degrade();
+
+        let pwm = Pwm::new(device.PWM0);
+        pwm.set_output_pin(
+            Channel::C0,
+            p0.p0_14.into_push_pull_output(Level::High).degrade(),
+        );
+        pwm.set_period(1000u32.hz());
+        pwm.enable();
+
+        let pins = UartePins {
+            rxd: p1.p1_07.into_floating_input().degrade(),
+            txd: p1.p1_08.into_push_pull_output(Level::High).degrade(),
+            cts: None,
+            rts: None,
+        };
+        let uarte = Uarte::new(device.UARTE1, pins, Parity::EXCLUDED, Baudrate::BAUD9600);
+        let (tx, rx) = uarte.split(uart_tx_buff, uart_rx_buff).unwrap();
+        let rx = DmaRx::new(rx, device.TIMER1, device.PPI, rx_chunks, RX_IDLE);
+
+        let dk = Dk {
+            led,
+            buttons,
+            tx,
+            rx,
+            pwm: DimmedLed(pwm),
+            gpiote: Gpiote::new(device.GPIOTE),
+        };
+        (dk, mono)
+    }
+}
+
+impl Board for Dk {
+    type Led = Pin<Output<PushPull>>;
+    type Button = Pin<Input<PullUp>>;
+    type Tx = UarteTx<UARTE1>;
+    type Rx = DmaRx;
+    type Pwm = DimmedLed;
+
+    fn led(&mut self) -> &mut Self::Led {
+        &mut self.led
+    }
+
+    fn buttons(&mut self) -> &mut [Self::Button] {
+        &mut self.buttons
+    }
+
+    fn uart(&mut self) -> (&mut Self::Tx, &mut Self::Rx) {
+        (&mut self.tx, &mut self.rx)
+    }
+
+    fn pwm(&mut self) -> &mut DimmedLed {
+        &mut self.pwm
+    }
+}
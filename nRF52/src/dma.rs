@@ -0,0 +1,210 @@
+//! UARTE1 reception by EasyDMA, a chunk of bytes per transfer instead of one.
+//!
+//! Two chunks take turns: while the application reads one, EasyDMA fills the other.
+//! A transfer ends when its chunk is full, or when the line stays silent: every byte
+//! (RXDRDY) restarts TIMER1 through the PPI, and when it reaches its compare value the
+//! PPI stops the reception (STOPRX). No interrupt per byte, no polling.
+//!
+//! Sending is EasyDMA too, a frame per transfer: `pump` starts the next one of a
+//! `TxQueue` when ENDTX says the previous one is out.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use appcore::{transport::Backlog, txqueue::TxQueue};
+use embedded_hal::serial::{self, Write};
+use nrf52840_hal::{
+    pac::{PPI, TIMER1, UARTE1},
+    uarte::{Error, UarteRx, UarteTx},
+};
+use protocol::Duration;
+use serde::Serialize;
+
+/// Bytes per EasyDMA transfer. A frame can span several transfers.
+pub const RX_CHUNK: usize = 16;
+
+pub struct DmaRx {
+    // Only keeps the HAL from reading too
+    _rx: UarteRx<UARTE1>,
+    _idle: TIMER1,
+    // EasyDMA writes the chunks behind our back
+    chunks: *const [[u8; RX_CHUNK]; 2],
+    /// The chunk EasyDMA is (or will be) writing.
+    filling: usize,
+    /// The chunk of the last transfer, and how much of it is read.
+    done: usize,
+    amount: usize,
+    read: usize,
+    receiving: bool,
+}
+
+// The pointer is only read, in whichever task owns the `DmaRx`
+unsafe impl Send for DmaRx {}
+
+impl DmaRx {
+    /// Starts receiving. The reception stops after `idle` without a byte, see
+    /// `protocol::accumulator::inter_byte_timeout`. `on_interrupt` must run on the
+    /// UARTE1 interrupt, or in `idle` once it is pended, see `button_05_c`.
+    pub fn new(
+        rx: UarteRx<UARTE1>,
+        timer: TIMER1,
+        ppi: PPI,
+        chunks: &'static mut [[u8; RX_CHUNK]; 2],
+        idle: Duration,
+    ) -> Self {
+        let uarte = unsafe { &*UARTE1::ptr() };
+
+        timer.prescaler.write(
+            |w| unsafe { w.prescaler().bits(4) }, // 1 MHz
+        );
+        timer.bitmode.write(|w| w.bitmode()._32bit());
+        timer.cc[0].write(|w| unsafe { w.cc().bits(idle.ticks()) });
+        timer
+            .shorts
+            .write(|w| w.compare0_clear().enabled().compare0_stop().enabled());
+
+        // RXDRDY -> clear and start TIMER1, COMPARE[0] -> STOPRX
+        ppi.ch[0]
+            .eep
+            .write(|w| unsafe { w.bits(&uarte.events_rxdrdy as *const _ as u32) });
+        ppi.ch[0]
+            .tep
+            .write(|w| unsafe { w.bits(&timer.tasks_clear as *const _ as u32) });
+        ppi.fork[0]
+            .tep
+            .write(|w| unsafe { w.bits(&timer.tasks_start as *const _ as u32) });
+        ppi.ch[1]
+            .eep
+            .write(|w| unsafe { w.bits(&timer.events_compare[0] as *const _ as u32) });
+        ppi.ch[1]
+            .tep
+            .write(|w| unsafe { w.bits(&uarte.tasks_stoprx as *const _ as u32) });
+        ppi.chenset.write(|w| w.ch0().set().ch1().set());
+
+        let chunks: *const [[u8; RX_CHUNK]; 2] = chunks;
+        uarte.events_rxstarted.reset();
+        uarte.events_endrx.reset();
+        uarte.events_rxto.reset();
+        uarte
+            .intenset
+            .write(|w| w.rxstarted().set().endrx().set().rxto().set());
+        let mut dma = DmaRx {
+            _rx: rx,
+            _idle: timer,
+            chunks,
+            filling: 0,
+            done: 0,
+            amount: 0,
+            read: 0,
+            receiving: false,
+        };
+        dma.start();
+        dma
+    }
+
+    fn chunk(&self, i: usize) -> u32 {
+        // SAFETY: in bounds, only the address is taken
+        unsafe { (*self.chunks)[i].as_ptr() as u32 }
+    }
+
+    fn start(&mut self) {
+        let uarte = unsafe { &*UARTE1::ptr() };
+        uarte
+            .rxd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(self.chunk(self.filling)) });
+        uarte
+            .rxd
+            .maxcnt
+            .write(|w| unsafe { w.maxcnt().bits(RX_CHUNK as _) });
+        compiler_fence(Ordering::SeqCst);
+        uarte.tasks_startrx.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Takes the chunk of the transfer that ended, and starts the next one.
+    pub fn on_interrupt(&mut self) {
+        let uarte = unsafe { &*UARTE1::ptr() };
+        if uarte.events_endrx.read().bits() != 0 {
+            uarte.events_endrx.reset();
+            compiler_fence(Ordering::SeqCst);
+            self.done = self.filling;
+            self.amount = uarte.rxd.amount.read().bits() as usize;
+            self.read = 0;
+            self.filling = 1 - self.filling;
+            self.receiving = false;
+            // A full chunk: more is coming, no time to wait for RXTO
+            if self.amount == RX_CHUNK {
+                self.start();
+            }
+        }
+        if uarte.events_rxto.read().bits() != 0 {
+            uarte.events_rxto.reset();
+            // The line went idle. What came after STOPRX waits in the FIFO, it goes in
+            // the next chunk.
+            if !self.receiving {
+                self.start();
+            }
+        }
+        if uarte.events_rxstarted.read().bits() != 0 {
+            uarte.events_rxstarted.reset();
+            self.receiving = true;
+        }
+    }
+}
+
+impl Backlog for DmaRx {
+    /// Of the last transfer, the bytes of the next one come with their own interrupt.
+    fn backlog(&self) -> usize {
+        self.amount - self.read
+    }
+}
+
+impl serial::Read<u8> for DmaRx {
+    type Error = Error;
+
+    /// The bytes of the last transfer. Read them all before the next one ends: the
+    /// chunk is written again by the transfer after.
+    fn read(&mut self) -> nb::Result<u8, Error> {
+        if self.read == self.amount {
+            return Err(nb::Error::WouldBlock);
+        }
+        // SAFETY: EasyDMA writes the other chunk
+        let byte = unsafe { core::ptr::read_volatile(&(*self.chunks)[self.done][self.read]) };
+        self.read += 1;
+        Ok(byte)
+    }
+}
+
+/// Makes UARTE1 pend at the end of every reception (ENDRX), each byte of a `UarteRx`:
+/// without a task on UARTE1, it wakes `nrfie::power::wait_for_event`.
+pub fn listen_endrx() {
+    let uarte = unsafe { &*UARTE1::ptr() };
+    uarte.intenset.write(|w| w.endrx().set());
+}
+
+/// Makes UARTE1 interrupt at the end of every transmission (ENDTX), see `pump`.
+pub fn listen_endtx() {
+    let uarte = unsafe { &*UARTE1::ptr() };
+    uarte.intenset.write(|w| w.endtx().set());
+}
+
+/// Starts sending the next frame of `queue`, unless one is still going out. Call it
+/// after a push and on the UARTE1 interrupt.
+pub fn pump<T: Copy + Serialize, const D: usize>(
+    tx: &mut UarteTx<UARTE1>,
+    queue: &mut TxQueue<T, D>,
+) {
+    // Also what tells the HAL the last frame is out
+    if let Err(nb::Error::WouldBlock) = tx.flush() {
+        return;
+    }
+    let uarte = unsafe { &*UARTE1::ptr() };
+    uarte.events_endtx.reset();
+    if let Some(frame) = queue.next_frame() {
+        // The HAL buffer holds a whole frame, see `uart_tx_buff`
+        for &byte in frame {
+            tx.write(byte).ok();
+        }
+        // Starts the transfer
+        tx.flush().ok();
+    }
+}
@@ -5,7 +5,10 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 use defmt_rtt as _; // global logger
 use nrf52840_hal as _; // memory layout
 
+pub mod board;
+pub mod dma;
 pub mod mono;
+pub mod power;
 
 use panic_probe as _;
 
@@ -0,0 +1,46 @@
+//! Sleeping in `idle`, and telling how much.
+//!
+//! Where every task is bound to an interrupt, `idle` only `sleep`s (WFI). Where `idle`
+//! polls the UART itself, it `wait_for_event`s (WFE): the UART interrupt is pended but
+//! not enabled, and with `wake_on_pending` its pending still wakes the core.
+
+use appcore::sleep::SleepMeter;
+use cortex_m::peripheral::SCB;
+use protocol::{Duration, Instant};
+
+/// How often `idle` reports the fraction of the time asleep.
+pub const SLEEP_REPORT_PERIOD: Duration = Duration::millis(10_000);
+
+/// Makes an interrupt that is pended, enabled or not, wake `wait_for_event`.
+pub fn wake_on_pending(scb: &mut SCB) {
+    // SEVONPEND, cortex-m 0.7.4 has no method for it
+    unsafe { scb.scr.modify(|scr| scr | 1 << 4) };
+}
+
+/// Sleeps until the next interrupt.
+pub fn sleep(meter: &mut SleepMeter, now: impl Fn() -> Instant) {
+    measure(meter, now, cortex_m::asm::wfi);
+}
+
+/// Sleeps until the next event, see `wake_on_pending`. It can return without one,
+/// poll again and call it again.
+pub fn wait_for_event(meter: &mut SleepMeter, now: impl Fn() -> Instant) {
+    measure(meter, now, cortex_m::asm::wfe);
+}
+
+fn measure(meter: &mut SleepMeter, now: impl Fn() -> Instant, wait: fn()) {
+    // The wake up is timestamped before the interrupt runs, its time counts as awake
+    let report = cortex_m::interrupt::free(|_| {
+        let before = now();
+        wait();
+        meter.slept(before, now())
+    });
+    if let Some(report) = report {
+        defmt::info!(
+            "Asleep {}.{}% of the time, {} wake ups.",
+            report.permille / 10,
+            report.permille % 10,
+            report.wakeups
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Builds frames out of the bytes as they come from the UART.
+//!
+//! The receivers used to push every byte into a `heapless::Vec` and ignore the result of
+//! `push`: a frame longer than the buffer lost its tail, and the next frame was glued to
+//! what was left. The `Accumulator` drops such a frame as a whole, tells so with
+//! `Feed::Overflow`, and starts over after the next `0x00`.
+//!
+//! A lost `0x00` is just as bad: the partial frame stays in the buffer and the next
+//! command is glued to it. With `with_timeout`, bytes fed with `feed_at` are timestamped
+//! and a partial frame is dropped when the line was silent for too long in the middle of it.
+
+use serde::de::DeserializeOwned;
+
+use crate::{frame::Stats, Duration, Error, Instant, MAX_FRAME_LEN};
+
+/// How long one character takes on the wire at `baud`, with 8N1 framing (10 bits).
+pub const fn char_time(baud: u32) -> Duration {
+    Duration::from_ticks(10 * 1_000_000 / baud)
+}
+
+/// The longest silence tolerated in the middle of a frame, in characters at `baud`.
+pub const fn inter_byte_timeout(baud: u32, chars: u32) -> Duration {
+    Duration::from_ticks(chars * char_time(baud).ticks())
+}
+
+/// What happened with the byte(s) that were fed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Feed<T> {
+    /// The frame is not complete yet.
+    Consumed,
+    /// A whole frame was received and decoded.
+    Frame(T),
+    /// The frame does not fit in the buffer. Its bytes are dropped until the next `0x00`.
+    Overflow,
+    /// A whole frame was received, but could not be decoded.
+    DecodeError(Error),
+    /// The line was silent for too long in the middle of a frame, its bytes were dropped.
+    /// The byte that was just fed starts a new frame.
+    Timeout,
+}
+
+pub struct Accumulator<const N: usize = MAX_FRAME_LEN> {
+    buf: [u8; N],
+    len: usize,
+    /// Set after an overflow, until the next `0x00`.
+    discarding: bool,
+    timeout: Option<Duration>,
+    /// When the previous byte was fed with `feed_at`.
+    last: Option<Instant>,
+    pub stats: Stats,
+}
+
+impl<const N: usize> Accumulator<N> {
+    pub const fn new() -> Self {
+        Accumulator {
+            buf: [0; N],
+            len: 0,
+            discarding: false,
+            timeout: None,
+            last: None,
+            stats: Stats {
+                received: 0,
+                crc_errors: 0,
+                decode_errors: 0,
+                overflows: 0,
+                timeouts: 0,
+            },
+        }
+    }
+
+    /// Drops a partial frame when no byte came for `timeout`, see `inter_byte_timeout`.
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Feeds one byte received at `now`.
+    pub fn feed_at<T: DeserializeOwned>(&mut self, byte: u8, now: Instant) -> Feed<T> {
+        let last = self.last.replace(now);
+        let stale = match (self.timeout, last) {
+            (Some(timeout), Some(last)) => now
+                .checked_duration_since(last)
+                .is_some_and(|gap| gap > timeout),
+            _ => false,
+        };
+        if stale && (self.len > 0 || self.discarding) {
+            // An overflowing frame was already counted
+            if !self.discarding {
+                self.stats.timeouts = self.stats.timeouts.wrapping_add(1);
+            }
+            self.clear();
+            // A single byte can neither overflow nor complete a frame
+            let _ = self.feed::<T>(byte);
+            return Feed::Timeout;
+        }
+        self.feed(byte)
+    }
+
+    /// Feeds one byte, without timestamp.
+    pub fn feed<T: DeserializeOwned>(&mut self, byte: u8) -> Feed<T> {
+        if byte == 0 {
+            let len = self.len;
+            self.len = 0;
+            if self.discarding {
+                self.discarding = false;
+                return Feed::Consumed;
+            }
+            // Several 0 in a row, there is no frame in between.
+            if len == 0 {
+                return Feed::Consumed;
+            }
+            return match self.stats.decode(&mut self.buf[..len]) {
+                Ok(msg) => Feed::Frame(msg),
+                Err(e) => Feed::DecodeError(e),
+            };
+        }
+        if self.discarding {
+            return Feed::Consumed;
+        }
+        if self.len == N {
+            self.len = 0;
+            self.discarding = true;
+            self.stats.overflows = self.stats.overflows.wrapping_add(1);
+            return Feed::Overflow;
+        }
+        self.buf[self.len] = byte;
+        self.len += 1;
+        Feed::Consumed
+    }
+
+    /// Feeds the bytes until something else than `Feed::Consumed` happens,
+    /// and returns the bytes that were not fed yet.
+    pub fn feed_slice<'a, T: DeserializeOwned>(&mut self, bytes: &'a [u8]) -> (Feed<T>, &'a [u8]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            match self.feed(byte) {
+                Feed::Consumed => {}
+                outcome => return (outcome, &bytes[i + 1..]),
+            }
+        }
+        (Feed::Consumed, &[])
+    }
+
+    /// Forgets the bytes of the current frame.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.discarding = false;
+    }
+}
+
+impl<const N: usize> Default for Accumulator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frame::encode, Command};
+
+    fn encoded(cmd: &Command) -> Vec<u8> {
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        encode(cmd, &mut buf).unwrap().to_vec()
+    }
+
+    fn feed_all(acc: &mut Accumulator, mut bytes: &[u8]) -> Vec<Feed<Command>> {
+        let mut outcomes = Vec::new();
+        while !bytes.is_empty() {
+            let (outcome, rest) = acc.feed_slice(bytes);
+            if outcome != Feed::Consumed {
+                outcomes.push(outcome);
+            }
+            bytes = rest;
+        }
+        outcomes
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let mut acc: Accumulator = Accumulator::new();
+        let bytes = encoded(&Command::Pwm(200));
+        let (last, rest) = bytes.split_last().unwrap();
+        for b in rest {
+            assert_eq!(acc.feed::<Command>(*b), Feed::Consumed);
+        }
+        assert_eq!(acc.feed(*last), Feed::Frame(Command::Pwm(200)));
+    }
+
+    #[test]
+    fn several_frames_in_one_slice() {
+        let mut acc = Accumulator::new();
+        let mut bytes = encoded(&Command::On);
+        bytes.extend(encoded(&Command::Interval(3)));
+        assert_eq!(
+            feed_all(&mut acc, &bytes),
+            vec![Feed::Frame(Command::On), Feed::Frame(Command::Interval(3))]
+        );
+        assert_eq!(acc.stats.received, 2);
+    }
+
+    #[test]
+    fn overflow_resyncs_on_the_next_delimiter() {
+        let mut acc = Accumulator::new();
+        let mut bytes = vec![0xaa; MAX_FRAME_LEN + 5];
+        bytes.push(0);
+        bytes.extend(encoded(&Command::Off));
+        assert_eq!(
+            feed_all(&mut acc, &bytes),
+            vec![Feed::Overflow, Feed::Frame(Command::Off)]
+        );
+        assert_eq!(acc.stats.overflows, 1);
+        assert_eq!(acc.stats.dropped(), 1);
+    }
+
+    #[test]
+    fn garbage_is_a_decode_error() {
+        let mut acc = Accumulator::new();
+        let mut bytes = vec![0x03, 0x01, 0x02, 0];
+        bytes.extend(encoded(&Command::On));
+        assert_eq!(
+            feed_all(&mut acc, &bytes),
+            vec![Feed::DecodeError(Error::Crc), Feed::Frame(Command::On)]
+        );
+    }
+
+    #[test]
+    fn a_frame_joined_midway_is_dropped() {
+        let mut acc = Accumulator::new();
+        let first = encoded(&Command::Pwm(100));
+        let mut bytes = first[2..].to_vec();
+        bytes.extend(encoded(&Command::Pwm(100)));
+        let outcomes = feed_all(&mut acc, &bytes);
+        assert!(matches!(outcomes[0], Feed::DecodeError(_)));
+        assert_eq!(outcomes[1], Feed::Frame(Command::Pwm(100)));
+    }
+
+    #[test]
+    fn empty_frames_are_ignored() {
+        let mut acc = Accumulator::new();
+        let mut bytes = vec![0, 0, 0];
+        bytes.extend(encoded(&Command::On));
+        assert_eq!(feed_all(&mut acc, &bytes), vec![Feed::Frame(Command::On)]);
+        assert_eq!(acc.stats.dropped(), 0);
+    }
+
+    const TIMEOUT: Duration = inter_byte_timeout(9600, 4);
+
+    /// Feeds the bytes `gap_us` apart, starting at `start_us`.
+    fn feed_timed(
+        acc: &mut Accumulator,
+        bytes: &[u8],
+        start_us: u32,
+        gap_us: u32,
+    ) -> Vec<Feed<Command>> {
+        let mut outcomes = Vec::new();
+        for (i, b) in bytes.iter().enumerate() {
+            let now = Instant::from_ticks(start_us + i as u32 * gap_us);
+            match acc.feed_at(*b, now) {
+                Feed::Consumed => {}
+                outcome => outcomes.push(outcome),
+            }
+        }
+        outcomes
+    }
+
+    #[test]
+    fn character_time() {
+        assert_eq!(char_time(9600), Duration::from_ticks(1041));
+        assert_eq!(char_time(115_200), Duration::from_ticks(86));
+        assert_eq!(TIMEOUT, Duration::from_ticks(4164));
+    }
+
+    #[test]
+    fn a_frame_at_line_rate_goes_through() {
+        let mut acc = Accumulator::new().with_timeout(TIMEOUT);
+        let bytes = encoded(&Command::Pwm(42));
+        let outcomes = feed_timed(&mut acc, &bytes, 0, char_time(9600).ticks());
+        assert_eq!(outcomes, vec![Feed::Frame(Command::Pwm(42))]);
+    }
+
+    #[test]
+    fn stale_partial_frame_is_dropped() {
+        let mut acc = Accumulator::new().with_timeout(TIMEOUT);
+        let good = encoded(&Command::Off);
+        // the 0 terminating this frame was lost
+        let partial = &encoded(&Command::Pwm(7))[..3];
+        assert!(feed_timed(&mut acc, partial, 0, 1000).is_empty());
+
+        let outcomes = feed_timed(&mut acc, &good, 2000 + TIMEOUT.ticks() + 1, 1000);
+        assert_eq!(outcomes, vec![Feed::Timeout, Feed::Frame(Command::Off)]);
+        assert_eq!(acc.stats.timeouts, 1);
+        assert_eq!(acc.stats.dropped(), 1);
+    }
+
+    #[test]
+    fn gap_between_frames_is_not_a_timeout() {
+        let mut acc = Accumulator::new().with_timeout(TIMEOUT);
+        let mut outcomes = feed_timed(&mut acc, &encoded(&Command::On), 0, 1000);
+        outcomes.extend(feed_timed(
+            &mut acc,
+            &encoded(&Command::Off),
+            1_000_000,
+            1000,
+        ));
+        assert_eq!(
+            outcomes,
+            vec![Feed::Frame(Command::On), Feed::Frame(Command::Off)]
+        );
+    }
+
+    #[test]
+    fn gap_of_exactly_the_timeout_is_tolerated() {
+        let mut acc = Accumulator::new().with_timeout(TIMEOUT);
+        let bytes = encoded(&Command::Interval(9));
+        let outcomes = feed_timed(&mut acc, &bytes, 0, TIMEOUT.ticks());
+        assert_eq!(outcomes, vec![Feed::Frame(Command::Interval(9))]);
+    }
+
+    #[test]
+    fn timeout_also_ends_an_overflow() {
+        let mut acc = Accumulator::new().with_timeout(TIMEOUT);
+        let garbage = [0xaa; MAX_FRAME_LEN + 1];
+        assert_eq!(feed_timed(&mut acc, &garbage, 0, 100), vec![Feed::Overflow]);
+        let outcomes = feed_timed(&mut acc, &encoded(&Command::On), 1_000_000, 100);
+        assert_eq!(outcomes, vec![Feed::Timeout, Feed::Frame(Command::On)]);
+        assert_eq!(acc.stats.dropped(), 1);
+    }
+
+    #[test]
+    fn without_timeout_nothing_goes_stale() {
+        let mut acc = Accumulator::new();
+        let bytes = encoded(&Command::On);
+        let outcomes = feed_timed(&mut acc, &bytes, 0, 1_000_000);
+        assert_eq!(outcomes, vec![Feed::Frame(Command::On)]);
+    }
+}
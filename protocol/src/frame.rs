@@ -0,0 +1,138 @@
+//! Frames protected by a CRC.
+//!
+//! COBS alone does not say if a byte was corrupted on the wire, postcard will happily
+//! turn a `Pwm(200)` with a flipped bit into a `Pwm(72)`. Here every message gets a
+//! CRC-16 trailer before being framed with COBS:
+//!
+//! ```text
+//! COBS( postcard(msg) ++ crc16(postcard(msg)).to_le_bytes() ) ++ 0
+//! ```
+//!
+//! The CRC is CRC-16/CCITT-FALSE (poly 0x1021, init 0xffff).
+
+use crc::{Crc, CRC_16_IBM_3740};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, MAX_FRAME_LEN};
+
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_IBM_3740);
+
+/// Length of the CRC trailer.
+pub const CRC_LEN: usize = 2;
+
+/// Serializes `msg`, appends its CRC and frames both with COBS into `buf`.
+/// The returned slice ends with the 0 terminating byte and can be written as is.
+pub fn encode<'a, T: Serialize>(msg: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8], Error> {
+    let mut raw = [0u8; MAX_FRAME_LEN];
+    let len = postcard::to_slice(msg, &mut raw[..MAX_FRAME_LEN - CRC_LEN])?.len();
+    let crc = CRC16.checksum(&raw[..len]);
+    raw[len..len + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    let raw = &raw[..len + CRC_LEN];
+
+    // + 1 for the terminating 0
+    let framed_len = cobs::max_encoding_length(raw.len()) + 1;
+    if framed_len > buf.len() {
+        return Err(postcard::Error::SerializeBufferFull.into());
+    }
+    let len = cobs::encode(raw, buf);
+    buf[len] = 0;
+    Ok(&mut buf[..len + 1])
+}
+
+/// Decodes a frame (with or without its terminating 0) in place and checks its CRC.
+/// Like `protocol::decode`, every byte of the message must be used.
+pub fn decode<'a, T: Deserialize<'a>>(frame: &'a mut [u8]) -> Result<T, Error> {
+    let frame = match frame {
+        [rest @ .., 0] => rest,
+        _ => frame,
+    };
+    let len = cobs::decode_in_place(frame).map_err(|_| Error::Cobs)?;
+    if len < CRC_LEN {
+        return Err(Error::Crc);
+    }
+    let (msg, crc) = frame[..len].split_at(len - CRC_LEN);
+    if CRC16.checksum(msg).to_le_bytes() != crc {
+        return Err(Error::Crc);
+    }
+    let (msg, unused) = postcard::take_from_bytes(msg)?;
+    if unused.is_empty() {
+        Ok(msg)
+    } else {
+        Err(Error::TrailingBytes)
+    }
+}
+
+/// Counts what happened to the received frames.
+/// Bad frames are dropped, but we want to know how many there were.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// Frames that passed the CRC and could be decoded.
+    pub received: u32,
+    /// Frames dropped because of a bad CRC.
+    pub crc_errors: u32,
+    /// Frames with a good CRC (or no CRC at all) that could still not be decoded.
+    pub decode_errors: u32,
+    /// Frames too long for the receive buffer, see `Accumulator`.
+    pub overflows: u32,
+    /// Partial frames dropped because the line went silent, see `Accumulator::with_timeout`.
+    pub timeouts: u32,
+}
+
+impl Stats {
+    /// Same as `decode`, but keeps count.
+    pub fn decode<'a, T: Deserialize<'a>>(&mut self, frame: &'a mut [u8]) -> Result<T, Error> {
+        let res = decode(frame);
+        match res {
+            Ok(_) => self.received = self.received.wrapping_add(1),
+            Err(Error::Crc) => self.crc_errors = self.crc_errors.wrapping_add(1),
+            Err(_) => self.decode_errors = self.decode_errors.wrapping_add(1),
+        }
+        res
+    }
+
+    /// Number of frames that were dropped, for any reason.
+    pub fn dropped(&self) -> u32 {
+        self.crc_errors
+            .wrapping_add(self.decode_errors)
+            .wrapping_add(self.overflows)
+            .wrapping_add(self.timeouts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Command;
+
+    #[test]
+    fn crc_is_ccitt_false() {
+        assert_eq!(CRC16.checksum(b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        let frame = encode(&Command::Pwm(200), &mut buf).unwrap();
+        assert_eq!(frame.last(), Some(&0));
+        assert_eq!(frame.iter().filter(|b| **b == 0).count(), 1);
+        assert_eq!(decode(frame), Ok(Command::Pwm(200)));
+    }
+
+    #[test]
+    fn corrupted_frame_is_counted() {
+        let mut stats = Stats::default();
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        let frame = encode(&Command::Pwm(200), &mut buf).unwrap();
+        frame[2] ^= 0x01;
+        assert_eq!(stats.decode::<Command>(frame), Err(Error::Crc));
+        assert_eq!(stats.crc_errors, 1);
+        assert_eq!(stats.dropped(), 1);
+    }
+
+    #[test]
+    fn too_small_buffer_is_an_error() {
+        let mut buf = [0u8; 4];
+        assert!(encode(&Command::Pwm(200), &mut buf).is_err());
+    }
+}
@@ -0,0 +1,368 @@
+//! The messages sent over the UART between the nRF52840 DK and the Nucleo.
+//!
+//! Both firmware crates (`nrfie` and `nucleis`) depend on this crate, so the sender and the
+//! receiver can never disagree on what a `Command` looks like on the wire.
+//! It is `no_std`, enable the `std` feature to use it from the host.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+use serde::{Deserialize, Serialize};
+
+pub mod accumulator;
+pub mod frame;
+pub mod link;
+pub mod reliable;
+
+/// Version of the wire format.
+/// Bump it whenever a variant is added, removed, reordered or when a field changes type.
+pub const VERSION: u8 = 8;
+
+/// Largest COBS frame (including the terminating 0) any message can take.
+/// Buffers on both boards are sized from this.
+pub const MAX_FRAME_LEN: usize = 16;
+
+/// Shortest time lit or dark of a `Command::Blink`, the led would only flicker.
+pub const MIN_BLINK_MS: u32 = 10;
+/// Longest time lit or dark of a `Command::Blink`, the timers of both boards wrap
+/// after ~71 minutes.
+pub const MAX_BLINK_MS: u32 = 3_600_000;
+
+/// Most characters of a `Command::Morse`, a longer text does not fit in a frame.
+pub const MORSE_LEN: usize = 9;
+
+/// An instant of the 1 MHz monotonic timers used on both boards
+/// (`nrfie::mono::MonoTimer` and `MonoTimer<TIM2/TIM5, 1_000_000>`).
+pub type Instant = fugit::TimerInstantU32<1_000_000>;
+/// A duration of the 1 MHz monotonic timers used on both boards.
+pub type Duration = fugit::TimerDurationU32<1_000_000>;
+
+/// This is the Command that will be sent instead of 0 or 1.
+/// u8 is the most logical container for `Pwm`, a pwm value between 0-255.
+/// The receiver scales it to `.get_max_duty()` of its pwm channel.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Command {
+    On,
+    Off,
+    Pwm(u8),
+    /// Blinking interval in seconds, as long lit as dark. 0 is steadily on.
+    Interval(u8),
+    /// "I am still here", see `link`.
+    Heartbeat,
+    /// Goes from the current level to the `Pwm` level `target` in `duration_ms`,
+    /// following `easing`. 0 ms jumps like `Pwm`.
+    Fade {
+        target: u8,
+        duration_ms: u16,
+        easing: Easing,
+    },
+    /// Plays `pattern` on the led instead of blinking, `repeat` times or for ever with 0.
+    Play {
+        pattern: Pattern,
+        repeat: u8,
+    },
+    /// Sets the step `index` of `Pattern::Uploaded` and drops the ones after it:
+    /// the steps are sent in order, from 0. The level is the one of `Pwm`.
+    Step {
+        index: u8,
+        level: u8,
+        duration_ms: u16,
+    },
+    /// Plays the ASCII `text` in Morse code like `Play`, see `Command::morse`.
+    /// The text ends at the first 0.
+    Morse {
+        text: [u8; MORSE_LEN],
+        repeat: u8,
+    },
+    /// Stops the pattern, the led blinks again.
+    Stop,
+    /// Blinks `on_ms` lit and `off_ms` dark, see `Command::blink`. 0 for either is
+    /// steadily on.
+    Blink {
+        on_ms: u32,
+        off_ms: u32,
+    },
+    /// `command` for the led of output `channel` only. The other commands are for
+    /// channel 0.
+    Channel {
+        channel: u8,
+        command: ChannelCommand,
+    },
+    /// The levels, like `Pwm`, of the RGB led on channels 1, 2 and 3.
+    Rgb {
+        red: u8,
+        green: u8,
+        blue: u8,
+    },
+}
+
+/// The commands a `Command::Channel` can carry, the ones that still fit in a frame
+/// with the channel. Each does what the `Command` of the same name does.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChannelCommand {
+    On,
+    Off,
+    Pwm(u8),
+    Fade {
+        target: u8,
+        duration_ms: u16,
+        easing: Easing,
+    },
+    Play {
+        pattern: Pattern,
+        repeat: u8,
+    },
+    Stop,
+    Blink {
+        on_ms: u32,
+        off_ms: u32,
+    },
+}
+
+impl From<ChannelCommand> for Command {
+    fn from(command: ChannelCommand) -> Self {
+        match command {
+            ChannelCommand::On => Command::On,
+            ChannelCommand::Off => Command::Off,
+            ChannelCommand::Pwm(level) => Command::Pwm(level),
+            ChannelCommand::Fade {
+                target,
+                duration_ms,
+                easing,
+            } => Command::Fade {
+                target,
+                duration_ms,
+                easing,
+            },
+            ChannelCommand::Play { pattern, repeat } => Command::Play { pattern, repeat },
+            ChannelCommand::Stop => Command::Stop,
+            ChannelCommand::Blink { on_ms, off_ms } => Command::Blink { on_ms, off_ms },
+        }
+    }
+}
+
+/// `ms` between `MIN_BLINK_MS` and `MAX_BLINK_MS`, 0 stays 0.
+pub fn clamp_blink_ms(ms: u32) -> u32 {
+    match ms {
+        0 => 0,
+        _ => ms.clamp(MIN_BLINK_MS, MAX_BLINK_MS),
+    }
+}
+
+impl Command {
+    /// `Command::Blink`, with the times the receiver can show.
+    pub fn blink(on_ms: u32, off_ms: u32) -> Command {
+        Command::Blink {
+            on_ms: clamp_blink_ms(on_ms),
+            off_ms: clamp_blink_ms(off_ms),
+        }
+    }
+
+    /// `Command::Blink` every `period_ms`, lit `percent` of it. 100 % or more is steadily
+    /// on, 0 % a flash of `MIN_BLINK_MS`.
+    pub fn blink_duty(period_ms: u32, percent: u8) -> Command {
+        let period_ms = clamp_blink_ms(period_ms);
+        let on_ms = u64::from(period_ms) * u64::from(percent.min(100)) / 100;
+        let on_ms = (on_ms as u32).max(MIN_BLINK_MS);
+        Command::blink(on_ms, period_ms.saturating_sub(on_ms))
+    }
+
+    /// `Command::Morse` of `text`, cut to `MORSE_LEN` bytes.
+    pub fn morse(text: &str, repeat: u8) -> Command {
+        let mut padded = [0; MORSE_LEN];
+        for (to, from) in padded.iter_mut().zip(text.bytes()) {
+            *to = from;
+        }
+        Command::Morse {
+            text: padded,
+            repeat,
+        }
+    }
+}
+
+/// The patterns of `Command::Play`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Pattern {
+    /// Slowly brighter and darker.
+    Breathing,
+    /// Two beats and a pause.
+    Heartbeat,
+    /// Short full flashes.
+    Strobe,
+    /// The steps sent with `Command::Step`.
+    Uploaded,
+}
+
+/// How a `Command::Fade` goes from one level to the other.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Easing {
+    Linear,
+    /// Slow at first.
+    EaseIn,
+    /// Slow at the end.
+    EaseOut,
+    /// Slow at both ends.
+    EaseInOut,
+    /// Doubles every tenth of the fade, for a long slow start.
+    Exponential,
+}
+
+/// What the receiver can answer on its (so far unused) tx line.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    Ack,
+    Nack,
+}
+
+/// Why a message could not be encoded or decoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The frame is not valid COBS.
+    Cobs,
+    /// The CRC of the frame does not match, it was corrupted on the wire.
+    Crc,
+    /// postcard failed: the buffer is too small, the variant is unknown...
+    Postcard(postcard::Error),
+    /// The message was decoded but some bytes were not used.
+    /// The sender and the receiver disagree on what the message looks like.
+    TrailingBytes,
+}
+
+impl From<postcard::Error> for Error {
+    fn from(e: postcard::Error) -> Self {
+        Error::Postcard(e)
+    }
+}
+
+/// Serializes `msg` with postcard and frames it with COBS into `buf`.
+/// This is the bare envelope, the boards talk with the CRC protected `frame::encode`.
+/// The returned slice ends with the 0 terminating byte and can be written as is.
+pub fn encode<'a, T: Serialize>(msg: &T, buf: &'a mut [u8]) -> Result<&'a mut [u8], Error> {
+    Ok(postcard::to_slice_cobs(msg, buf)?)
+}
+
+/// Decodes a COBS frame (with or without its terminating 0) in place.
+///
+/// Contrary to `postcard::from_bytes_cobs`, every byte of the frame must be used.
+/// The terminating 0 is not handed to postcard either, otherwise a receiver expecting
+/// a `u16` where a `u8` was sent would happily use the 0 as the high byte.
+pub fn decode<'a, T: Deserialize<'a>>(frame: &'a mut [u8]) -> Result<T, Error> {
+    let frame = match frame {
+        [rest @ .., 0] => rest,
+        _ => frame,
+    };
+    let len = cobs::decode_in_place(frame).map_err(|_| Error::Cobs)?;
+    let (msg, unused) = postcard::take_from_bytes(&frame[..len])?;
+    if unused.is_empty() {
+        Ok(msg)
+    } else {
+        Err(Error::TrailingBytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_round_trip() {
+        for cmd in [
+            Command::On,
+            Command::Off,
+            Command::Pwm(0),
+            Command::Pwm(255),
+            Command::Interval(1),
+            Command::Fade {
+                target: 255,
+                duration_ms: u16::MAX,
+                easing: Easing::Exponential,
+            },
+            Command::morse("HELLO WOR", 255),
+            Command::Blink {
+                on_ms: u32::MAX,
+                off_ms: u32::MAX,
+            },
+            Command::Channel {
+                channel: 255,
+                command: ChannelCommand::Blink {
+                    on_ms: u32::MAX,
+                    off_ms: u32::MAX,
+                },
+            },
+            Command::Rgb {
+                red: 255,
+                green: 0,
+                blue: 128,
+            },
+        ] {
+            let mut buf = [0u8; MAX_FRAME_LEN];
+            let frame = encode(&cmd, &mut buf).unwrap();
+            assert_eq!(frame.last(), Some(&0));
+            assert_eq!(decode::<Command>(frame).unwrap(), cmd);
+        }
+    }
+
+    #[test]
+    fn morse_is_padded_or_cut() {
+        let Command::Morse { text, repeat } = Command::morse("SOS", 2) else {
+            unreachable!()
+        };
+        assert_eq!((&text, repeat), (b"SOS\0\0\0\0\0\0", 2));
+        let Command::Morse { text, .. } = Command::morse("HELLO WORLD", 0) else {
+            unreachable!()
+        };
+        assert_eq!(&text, b"HELLO WOR");
+    }
+
+    #[test]
+    fn blink_times_are_clamped() {
+        assert_eq!(
+            Command::blink(0, 1),
+            Command::Blink {
+                on_ms: 0,
+                off_ms: MIN_BLINK_MS
+            }
+        );
+        assert_eq!(
+            Command::blink(500, u32::MAX),
+            Command::Blink {
+                on_ms: 500,
+                off_ms: MAX_BLINK_MS
+            }
+        );
+        assert_eq!(Command::blink_duty(1000, 25), Command::blink(250, 750));
+        assert_eq!(Command::blink_duty(1000, 0), Command::blink(10, 990));
+        assert_eq!(Command::blink_duty(1000, 100), Command::blink(1000, 0));
+        assert_eq!(Command::blink_duty(1000, 255), Command::blink(1000, 0));
+        assert_eq!(
+            Command::blink_duty(u32::MAX, 50),
+            Command::blink(MAX_BLINK_MS / 2, MAX_BLINK_MS / 2)
+        );
+    }
+
+    #[test]
+    fn response_round_trip() {
+        for resp in [Response::Ack, Response::Nack] {
+            let mut buf = [0u8; MAX_FRAME_LEN];
+            let frame = encode(&resp, &mut buf).unwrap();
+            assert_eq!(decode::<Response>(frame).unwrap(), resp);
+        }
+    }
+
+    #[test]
+    fn unused_bytes_are_an_error() {
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        let frame = encode(&Command::Pwm(200), &mut buf).unwrap();
+        assert_eq!(decode::<u8>(frame), Err(Error::TrailingBytes));
+    }
+
+    #[test]
+    fn too_small_buffer_is_an_error() {
+        let mut buf = [0u8; 2];
+        assert!(encode(&Command::Pwm(200), &mut buf).is_err());
+    }
+}
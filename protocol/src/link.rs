@@ -0,0 +1,112 @@
+//! Knowing if the other board is still there.
+//!
+//! The sender sends `Command::Heartbeat` every `HEARTBEAT_PERIOD`. On the receiver, the
+//! `Supervisor` is told about every frame that arrived, and declares the link down when
+//! nothing came for `max_missed` periods. The receiver can then go to a failsafe instead
+//! of acting on the last command forever.
+
+use crate::{Duration, Instant};
+
+/// How often the sender sends `Command::Heartbeat`.
+pub const HEARTBEAT_PERIOD: Duration = Duration::from_ticks(500_000);
+
+/// A change of the state of the link, to be logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Transition {
+    Up,
+    Down,
+}
+
+pub struct Supervisor {
+    period: Duration,
+    max_missed: u32,
+    last_seen: Option<Instant>,
+    up: bool,
+    /// How many times the link went down.
+    pub losses: u32,
+}
+
+impl Supervisor {
+    /// The link goes down when no frame came for `max_missed` times `period`.
+    /// It starts down, until the first frame comes.
+    pub const fn new(period: Duration, max_missed: u32) -> Self {
+        Supervisor {
+            period,
+            max_missed,
+            last_seen: None,
+            up: false,
+            losses: 0,
+        }
+    }
+
+    /// To be called for every valid frame, heartbeat or not.
+    pub fn on_frame(&mut self, now: Instant) -> Option<Transition> {
+        self.last_seen = Some(now);
+        if self.up {
+            None
+        } else {
+            self.up = true;
+            Some(Transition::Up)
+        }
+    }
+
+    /// To be called periodically, checks if too many heartbeats were missed.
+    pub fn poll(&mut self, now: Instant) -> Option<Transition> {
+        let last_seen = self.last_seen?;
+        let silence = now.checked_duration_since(last_seen)?;
+        if self.up && silence > self.period * self.max_missed {
+            self.up = false;
+            self.losses = self.losses.wrapping_add(1);
+            Some(Transition::Down)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(ms * 1000)
+    }
+
+    #[test]
+    fn starts_down_until_the_first_frame() {
+        let mut link = Supervisor::new(HEARTBEAT_PERIOD, 3);
+        assert!(!link.is_up());
+        assert_eq!(link.poll(at(10_000)), None);
+        assert_eq!(link.on_frame(at(10_000)), Some(Transition::Up));
+        assert_eq!(link.on_frame(at(10_500)), None);
+        assert!(link.is_up());
+    }
+
+    #[test]
+    fn goes_down_after_missed_heartbeats() {
+        let mut link = Supervisor::new(HEARTBEAT_PERIOD, 3);
+        link.on_frame(at(0));
+        assert_eq!(link.poll(at(1500)), None);
+        assert_eq!(link.poll(at(1501)), Some(Transition::Down));
+        assert_eq!(link.poll(at(3000)), None);
+        assert!(!link.is_up());
+        assert_eq!(link.losses, 1);
+    }
+
+    #[test]
+    fn comes_back_with_the_heartbeats() {
+        let mut link = Supervisor::new(HEARTBEAT_PERIOD, 2);
+        for ms in (0..5000).step_by(500) {
+            link.on_frame(at(ms));
+            assert_eq!(link.poll(at(ms + 400)), None);
+        }
+        assert_eq!(link.poll(at(6000)), Some(Transition::Down));
+        assert_eq!(link.on_frame(at(9000)), Some(Transition::Up));
+        assert_eq!(link.poll(at(9100)), None);
+    }
+}
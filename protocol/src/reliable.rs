@@ -0,0 +1,298 @@
+//! Acknowledged delivery of commands.
+//!
+//! Stop and wait: the sender numbers every `Request` and the receiver answers with a
+//! `Reply` carrying the same number. Without an answer, the request is sent again after
+//! a timeout, a bounded number of times. The receiver remembers the last number it
+//! applied, so a request sent again because its `Ack` got lost is not applied twice.
+//!
+//! A new command does not wait for the previous one: the led should show what the
+//! buttons say *now*, so the new request supersedes the one waiting for its `Ack`.
+//!
+//! Nothing in here touches the hardware. The firmware feeds the state machines with
+//! the received frames and the `now()` of its monotonic.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Command, Duration, Instant, Response};
+
+/// A command that must be acknowledged.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Request {
+    pub seq: u8,
+    pub command: Command,
+}
+
+/// The answer of the receiver to the `Request` with the same `seq`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Reply {
+    pub seq: u8,
+    pub response: Response,
+}
+
+/// What the sender must do next.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Action {
+    /// Write this request on the wire, it is sent again.
+    Transmit(Request),
+    /// This request was sent `max_retries` more times without an `Ack`, it is dropped.
+    GiveUp(Request),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SenderStats {
+    /// Requests that were acknowledged.
+    pub delivered: u32,
+    /// Requests sent again, after a timeout or a `Nack`.
+    pub retransmissions: u32,
+    /// Requests given up after too many retries.
+    pub lost: u32,
+    /// Requests replaced by a newer command before their `Ack` came.
+    pub superseded: u32,
+}
+
+struct Pending {
+    request: Request,
+    deadline: Instant,
+    retries: u8,
+}
+
+/// The sending side, on the nRF52.
+pub struct Sender {
+    next_seq: u8,
+    pending: Option<Pending>,
+    timeout: Duration,
+    max_retries: u8,
+    pub stats: SenderStats,
+}
+
+impl Sender {
+    /// A request is sent again when no `Reply` came after `timeout`, at most `max_retries` times.
+    pub fn new(timeout: Duration, max_retries: u8) -> Self {
+        Sender {
+            next_seq: 0,
+            pending: None,
+            timeout,
+            max_retries,
+            stats: SenderStats::default(),
+        }
+    }
+
+    /// Numbers the command. The returned request must be written on the wire.
+    pub fn send(&mut self, command: Command, now: Instant) -> Request {
+        if self.pending.is_some() {
+            self.stats.superseded = self.stats.superseded.wrapping_add(1);
+        }
+        let request = Request {
+            seq: self.next_seq,
+            command,
+        };
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.pending = Some(Pending {
+            request,
+            deadline: now + self.timeout,
+            retries: 0,
+        });
+        request
+    }
+
+    /// Handles a `Reply` from the receiver. Replies to older requests are ignored.
+    pub fn on_reply(&mut self, reply: Reply, now: Instant) -> Option<Action> {
+        match &self.pending {
+            Some(pending) if pending.request.seq == reply.seq => match reply.response {
+                Response::Ack => {
+                    self.pending = None;
+                    self.stats.delivered = self.stats.delivered.wrapping_add(1);
+                    None
+                }
+                Response::Nack => Some(self.retry(now)),
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks if the request waiting for its `Ack` timed out.
+    pub fn poll(&mut self, now: Instant) -> Option<Action> {
+        match &self.pending {
+            Some(pending) if now >= pending.deadline => Some(self.retry(now)),
+            _ => None,
+        }
+    }
+
+    /// When `poll` should be called next.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|p| p.deadline)
+    }
+
+    /// `true` when every request was either acknowledged or given up.
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    fn retry(&mut self, now: Instant) -> Action {
+        // Only called with a pending request.
+        let mut pending = self.pending.take().unwrap();
+        if pending.retries >= self.max_retries {
+            self.stats.lost = self.stats.lost.wrapping_add(1);
+            return Action::GiveUp(pending.request);
+        }
+        pending.retries += 1;
+        pending.deadline = now + self.timeout;
+        let request = pending.request;
+        self.pending = Some(pending);
+        self.stats.retransmissions = self.stats.retransmissions.wrapping_add(1);
+        Action::Transmit(request)
+    }
+}
+
+/// The receiving side, on the Nucleo.
+#[derive(Default)]
+pub struct Receiver {
+    last: Option<u8>,
+    /// Requests that were received again, and acknowledged without being applied.
+    pub duplicates: u32,
+}
+
+impl Receiver {
+    pub fn new() -> Self {
+        Receiver::default()
+    }
+
+    /// Acknowledges the request, and returns its command unless it was already applied.
+    pub fn receive(&mut self, request: Request) -> (Reply, Option<Command>) {
+        let reply = Reply {
+            seq: request.seq,
+            response: Response::Ack,
+        };
+        if self.last == Some(request.seq) {
+            self.duplicates = self.duplicates.wrapping_add(1);
+            return (reply, None);
+        }
+        self.last = Some(request.seq);
+        (reply, Some(request.command))
+    }
+
+    /// What to answer to a frame that had to be dropped.
+    /// The sender sends its request again right away instead of waiting for the timeout.
+    pub fn reject(&self) -> Reply {
+        Reply {
+            seq: self.last.map_or(0, |seq| seq.wrapping_add(1)),
+            response: Response::Nack,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fugit::ExtU32;
+
+    const TIMEOUT: Duration = Duration::from_ticks(50_000);
+
+    fn at(ms: u32) -> Instant {
+        Instant::from_ticks(0) + ms.millis()
+    }
+
+    #[test]
+    fn ack_clears_the_request() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let mut receiver = Receiver::new();
+        let request = sender.send(Command::On, at(0));
+        let (reply, cmd) = receiver.receive(request);
+        assert_eq!(cmd, Some(Command::On));
+        assert_eq!(sender.on_reply(reply, at(10)), None);
+        assert!(sender.is_idle());
+        assert_eq!(sender.poll(at(1000)), None);
+        assert_eq!(sender.stats.delivered, 1);
+    }
+
+    #[test]
+    fn retransmits_after_the_timeout() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let request = sender.send(Command::Pwm(8), at(0));
+        assert_eq!(sender.poll(at(49)), None);
+        assert_eq!(sender.poll(at(50)), Some(Action::Transmit(request)));
+        assert_eq!(sender.deadline(), Some(at(100)));
+        assert_eq!(sender.stats.retransmissions, 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let mut sender = Sender::new(TIMEOUT, 2);
+        let request = sender.send(Command::Off, at(0));
+        assert_eq!(sender.poll(at(50)), Some(Action::Transmit(request)));
+        assert_eq!(sender.poll(at(100)), Some(Action::Transmit(request)));
+        assert_eq!(sender.poll(at(150)), Some(Action::GiveUp(request)));
+        assert!(sender.is_idle());
+        assert_eq!(sender.poll(at(200)), None);
+        assert_eq!(sender.stats.lost, 1);
+    }
+
+    #[test]
+    fn nack_retransmits_right_away() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let mut receiver = Receiver::new();
+        let first = sender.send(Command::On, at(0));
+        sender.on_reply(receiver.receive(first).0, at(5));
+
+        let second = sender.send(Command::Interval(2), at(10));
+        // the second request was corrupted on the wire
+        let nack = receiver.reject();
+        assert_eq!(
+            sender.on_reply(nack, at(15)),
+            Some(Action::Transmit(second))
+        );
+        assert_eq!(receiver.receive(second).1, Some(Command::Interval(2)));
+    }
+
+    #[test]
+    fn lost_ack_is_not_applied_twice() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let mut receiver = Receiver::new();
+        let request = sender.send(Command::Pwm(16), at(0));
+        // the reply is lost
+        assert_eq!(receiver.receive(request).1, Some(Command::Pwm(16)));
+        let Some(Action::Transmit(again)) = sender.poll(at(50)) else {
+            panic!("not sent again");
+        };
+        let (reply, cmd) = receiver.receive(again);
+        assert_eq!(cmd, None);
+        assert_eq!(receiver.duplicates, 1);
+        assert_eq!(sender.on_reply(reply, at(55)), None);
+        assert_eq!(sender.stats.delivered, 1);
+    }
+
+    #[test]
+    fn new_command_supersedes_the_pending_one() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let old = sender.send(Command::Pwm(8), at(0));
+        let new = sender.send(Command::Pwm(16), at(10));
+        assert_ne!(old.seq, new.seq);
+        // a late ack for the old request does not clear the new one
+        let late = Reply {
+            seq: old.seq,
+            response: Response::Ack,
+        };
+        assert_eq!(sender.on_reply(late, at(20)), None);
+        assert!(!sender.is_idle());
+        assert_eq!(sender.poll(at(60)), Some(Action::Transmit(new)));
+        assert_eq!(sender.stats.superseded, 1);
+    }
+
+    #[test]
+    fn sequence_numbers_wrap() {
+        let mut sender = Sender::new(TIMEOUT, 3);
+        let mut receiver = Receiver::new();
+        for i in 0..600u32 {
+            let request = sender.send(Command::Interval(1), at(i));
+            assert_eq!(request.seq, i as u8);
+            let (reply, cmd) = receiver.receive(request);
+            assert_eq!(cmd, Some(Command::Interval(1)));
+            sender.on_reply(reply, at(i));
+        }
+        assert_eq!(receiver.duplicates, 0);
+    }
+}
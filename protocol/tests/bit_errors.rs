@@ -0,0 +1,86 @@
+//! Flips bits in valid frames, as a noisy 9600 baud wire would, and checks that the
+//! receiver drops them instead of acting on a wrong command.
+
+use protocol::{
+    frame::{encode, Stats},
+    Command, MAX_FRAME_LEN,
+};
+
+fn commands() -> Vec<Command> {
+    let mut all = vec![Command::On, Command::Off];
+    all.extend((0..=u8::MAX).map(Command::Pwm));
+    all.extend((0..=u8::MAX).map(Command::Interval));
+    all.push(Command::Heartbeat);
+    all
+}
+
+fn frame(cmd: &Command) -> Vec<u8> {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    encode(cmd, &mut buf).unwrap().to_vec()
+}
+
+/// Flips the given bits of the frame, without touching the terminating 0:
+/// a corrupted terminator splits the frame in two, which is a different story.
+fn corrupt(frame: &[u8], bits: &[usize]) -> Vec<u8> {
+    let mut corrupted = frame.to_vec();
+    for bit in bits {
+        corrupted[bit / 8] ^= 1 << (bit % 8);
+    }
+    corrupted
+}
+
+#[test]
+fn every_single_bit_error_is_dropped() {
+    let mut stats = Stats::default();
+    let mut injected = 0;
+    for cmd in commands() {
+        let frame = frame(&cmd);
+        for bit in 0..(frame.len() - 1) * 8 {
+            let mut corrupted = corrupt(&frame, &[bit]);
+            let res = stats.decode::<Command>(&mut corrupted);
+            assert!(
+                res.is_err(),
+                "{:?} with bit {} flipped gave {:?}",
+                cmd,
+                bit,
+                res
+            );
+            injected += 1;
+        }
+    }
+    assert_eq!(stats.received, 0);
+    assert_eq!(stats.dropped(), injected);
+}
+
+#[test]
+fn every_double_bit_error_is_dropped() {
+    let mut stats = Stats::default();
+    for cmd in commands() {
+        let frame = frame(&cmd);
+        let bits = (frame.len() - 1) * 8;
+        for first in 0..bits {
+            for second in first + 1..bits {
+                let mut corrupted = corrupt(&frame, &[first, second]);
+                let res = stats.decode::<Command>(&mut corrupted);
+                assert!(
+                    res.is_err(),
+                    "{:?} with bits {} and {} flipped gave {:?}",
+                    cmd,
+                    first,
+                    second,
+                    res
+                );
+            }
+        }
+    }
+    assert_eq!(stats.received, 0);
+}
+
+#[test]
+fn good_frames_still_go_through() {
+    let mut stats = Stats::default();
+    for cmd in commands() {
+        assert_eq!(stats.decode(&mut frame(&cmd)), Ok(cmd));
+    }
+    assert_eq!(stats.dropped(), 0);
+}
@@ -0,0 +1,164 @@
+//! Byte exact snapshots of every message as it travels on the wire.
+//!
+//! `tests/snapshots/wire.txt` is also the spec for tools that are not written in Rust.
+//! When the encoding changes on purpose, bump `protocol::VERSION` and regenerate it with
+//!
+//! ```terminal
+//! UPDATE_SNAPSHOTS=1 cargo test --test snapshots
+//! ```
+
+use std::{env, fmt::Debug, fs, path::PathBuf};
+
+use protocol::{
+    frame::encode,
+    reliable::{Reply, Request},
+    ChannelCommand, Command, Easing, Pattern, Response, MAX_FRAME_LEN, VERSION,
+};
+use serde::Serialize;
+
+fn snapshot_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots/wire.txt")
+}
+
+fn line<T: Serialize + Debug>(prefix: &str, msg: &T) -> String {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    let frame = encode(msg, &mut buf).unwrap();
+    let hex: Vec<String> = frame.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{:?} = {}\n", prefix, msg, hex.join(" "))
+}
+
+fn render() -> String {
+    let mut out = format!(
+        "# Wire format of the messages between the nRF52 and the Nucleo, version {}.\n\
+         # <message> = COBS(<postcard bytes> <CRC-16/CCITT-FALSE of them, little endian>) 00\n\
+         # This file is generated by `tests/snapshots.rs`, do not edit it by hand.\n",
+        VERSION
+    );
+    let edges = [0, 1, 127, 128, 254, 255];
+    let mut commands = vec![Command::On, Command::Off];
+    commands.extend(edges.iter().map(|&level| Command::Pwm(level)));
+    commands.extend(edges.iter().map(|&sec| Command::Interval(sec)));
+    commands.push(Command::Heartbeat);
+    for (target, duration_ms, easing) in [
+        (0, 0, Easing::Linear),
+        (255, 1, Easing::EaseIn),
+        (128, 256, Easing::EaseOut),
+        (1, 1000, Easing::EaseInOut),
+        (254, u16::MAX, Easing::Exponential),
+    ] {
+        commands.push(Command::Fade {
+            target,
+            duration_ms,
+            easing,
+        });
+    }
+    for pattern in [
+        Pattern::Breathing,
+        Pattern::Heartbeat,
+        Pattern::Strobe,
+        Pattern::Uploaded,
+    ] {
+        commands.push(Command::Play { pattern, repeat: 0 });
+    }
+    commands.push(Command::Play {
+        pattern: Pattern::Strobe,
+        repeat: 255,
+    });
+    commands.push(Command::Step {
+        index: 0,
+        level: 255,
+        duration_ms: 1,
+    });
+    commands.push(Command::Step {
+        index: 255,
+        level: 0,
+        duration_ms: u16::MAX,
+    });
+    commands.push(Command::morse("", 0));
+    commands.push(Command::morse("SOS", 3));
+    commands.push(Command::morse("HELLO WOR", 255));
+    commands.push(Command::Stop);
+    for (on_ms, off_ms) in [(0, 0), (10, 990), (1000, 1000), (65_536, 3_600_000)] {
+        commands.push(Command::Blink { on_ms, off_ms });
+    }
+    for (channel, command) in [
+        (0, ChannelCommand::On),
+        (1, ChannelCommand::Off),
+        (2, ChannelCommand::Pwm(128)),
+        (
+            3,
+            ChannelCommand::Fade {
+                target: 255,
+                duration_ms: 1000,
+                easing: Easing::EaseInOut,
+            },
+        ),
+        (
+            255,
+            ChannelCommand::Play {
+                pattern: Pattern::Breathing,
+                repeat: 0,
+            },
+        ),
+        (1, ChannelCommand::Stop),
+        (
+            2,
+            ChannelCommand::Blink {
+                on_ms: 100,
+                off_ms: 3_600_000,
+            },
+        ),
+    ] {
+        commands.push(Command::Channel { channel, command });
+    }
+    for (red, green, blue) in [(0, 0, 0), (255, 128, 1)] {
+        commands.push(Command::Rgb { red, green, blue });
+    }
+    for cmd in &commands {
+        out += &line("Command::", cmd);
+    }
+    for resp in &[Response::Ack, Response::Nack] {
+        out += &line("Response::", resp);
+    }
+    for seq in [0, 255] {
+        out += &line(
+            "",
+            &Request {
+                seq,
+                command: Command::Pwm(128),
+            },
+        );
+        out += &line(
+            "",
+            &Reply {
+                seq,
+                response: Response::Ack,
+            },
+        );
+    }
+    out
+}
+
+#[test]
+fn wire_format_matches_snapshot() {
+    let actual = render();
+    let path = snapshot_path();
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    for (expected, actual) in expected.lines().zip(actual.lines()) {
+        assert_eq!(
+            expected, actual,
+            "the wire format changed, if it is on purpose bump VERSION and run \
+             `UPDATE_SNAPSHOTS=1 cargo test --test snapshots`"
+        );
+    }
+    assert_eq!(
+        expected.lines().count(),
+        actual.lines().count(),
+        "messages were added or removed, run `UPDATE_SNAPSHOTS=1 cargo test --test snapshots`"
+    );
+}
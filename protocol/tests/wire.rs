@@ -0,0 +1,236 @@
+//! Sends every message through the same steps as the firmware does, and checks that what
+//! comes out on the other board is what went in.
+//!
+//! * the nRF52 `send_command` task encodes into a local `[u8; MAX_FRAME_LEN]`
+//!   (`frame::encode`) and writes the bytes one by one.
+//! * the Nucleo `parse` task pushes the bytes one by one into a `heapless::Vec` and
+//!   decodes (`frame::decode`) when the terminating 0 arrives.
+
+use heapless::Vec;
+use protocol::{
+    frame::{decode, encode},
+    reliable::{Reply, Request},
+    ChannelCommand, Command, Easing, Pattern, Response, MAX_FRAME_LEN, MORSE_LEN,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// What the nRF52 `send_command` task puts on the wire.
+fn send_command<T: Serialize>(msg: &T) -> std::vec::Vec<u8> {
+    let mut buf = [0u8; MAX_FRAME_LEN];
+    let data = encode(msg, &mut buf).expect("the message does not fit in the tx buffer");
+    data.to_vec()
+}
+
+/// What the Nucleo `parse` task makes out of the bytes, one decoded message per 0.
+fn parse<T: DeserializeOwned>(wire: &[u8]) -> std::vec::Vec<Result<T, protocol::Error>> {
+    let mut buf: Vec<u8, MAX_FRAME_LEN> = Vec::new();
+    let mut received = std::vec::Vec::new();
+    for &d in wire {
+        buf.push(d)
+            .expect("the frame does not fit in the rx buffer");
+        if d == 0 {
+            received.push(decode(&mut buf));
+            buf.clear();
+        }
+    }
+    assert!(buf.is_empty(), "bytes left without a terminating 0");
+    received
+}
+
+/// The variant index postcard puts in the first byte.
+/// Adding a variant to `Command` breaks this match, and `every_command` must then cover it.
+fn variant(cmd: &Command) -> usize {
+    match cmd {
+        Command::On => 0,
+        Command::Off => 1,
+        Command::Pwm(_) => 2,
+        Command::Interval(_) => 3,
+        Command::Heartbeat => 4,
+        Command::Fade { .. } => 5,
+        Command::Play { .. } => 6,
+        Command::Step { .. } => 7,
+        Command::Morse { .. } => 8,
+        Command::Stop => 9,
+        Command::Blink { .. } => 10,
+        Command::Channel { .. } => 11,
+        Command::Rgb { .. } => 12,
+    }
+}
+const VARIANTS: usize = 13;
+const EASINGS: [Easing; 5] = [
+    Easing::Linear,
+    Easing::EaseIn,
+    Easing::EaseOut,
+    Easing::EaseInOut,
+    Easing::Exponential,
+];
+
+/// Every variant, with every value its field can take.
+fn every_command() -> std::vec::Vec<Command> {
+    let mut all = vec![Command::On, Command::Off];
+    all.extend((0..=u8::MAX).map(Command::Pwm));
+    all.extend((0..=u8::MAX).map(Command::Interval));
+    all.push(Command::Heartbeat);
+    // every easing, with durations whose bytes are 0 or not
+    for easing in EASINGS {
+        for duration_ms in [0, 1, 255, 256, 1000, u16::MAX] {
+            for target in [0, 255] {
+                all.push(Command::Fade {
+                    target,
+                    duration_ms,
+                    easing,
+                });
+            }
+        }
+    }
+    for pattern in [
+        Pattern::Breathing,
+        Pattern::Heartbeat,
+        Pattern::Strobe,
+        Pattern::Uploaded,
+    ] {
+        all.extend((0..=u8::MAX).map(|repeat| Command::Play { pattern, repeat }));
+    }
+    for index in [0, 1, 255] {
+        for level in [0, 255] {
+            for duration_ms in [0, 1, 256, u16::MAX] {
+                all.push(Command::Step {
+                    index,
+                    level,
+                    duration_ms,
+                });
+            }
+        }
+    }
+    // the longest text, of every byte, and the empty one
+    all.extend((0..=u8::MAX).map(|byte| Command::Morse {
+        text: [byte; MORSE_LEN],
+        repeat: byte,
+    }));
+    all.push(Command::morse("", 0));
+    all.push(Command::Stop);
+    for on_ms in [0, 1, 256, 65_536, 16_777_216, u32::MAX] {
+        for off_ms in [0, 1000, u32::MAX] {
+            all.push(Command::Blink { on_ms, off_ms });
+        }
+    }
+    // every command of a channel, at its largest
+    for channel in [0, 1, 255] {
+        for command in [
+            ChannelCommand::On,
+            ChannelCommand::Off,
+            ChannelCommand::Pwm(255),
+            ChannelCommand::Fade {
+                target: 255,
+                duration_ms: u16::MAX,
+                easing: Easing::Exponential,
+            },
+            ChannelCommand::Play {
+                pattern: Pattern::Uploaded,
+                repeat: 255,
+            },
+            ChannelCommand::Stop,
+            ChannelCommand::Blink {
+                on_ms: u32::MAX,
+                off_ms: u32::MAX,
+            },
+        ] {
+            all.push(Command::Channel { channel, command });
+        }
+    }
+    for level in [0, 1, 255] {
+        all.push(Command::Rgb {
+            red: level,
+            green: 255 - level,
+            blue: level,
+        });
+    }
+    all
+}
+
+#[test]
+fn every_variant_is_covered() {
+    let mut seen = [false; VARIANTS];
+    for cmd in every_command() {
+        seen[variant(&cmd)] = true;
+    }
+    assert!(seen.iter().all(|s| *s), "a variant is missing: {:?}", seen);
+}
+
+#[test]
+fn every_command_survives_the_wire() {
+    for cmd in every_command() {
+        let wire = send_command(&cmd);
+        assert!(wire.len() <= MAX_FRAME_LEN);
+        let received = parse::<Command>(&wire);
+        assert_eq!(received.len(), 1, "{:?} was not one frame", cmd);
+        assert_eq!(received[0], Ok(cmd), "{:?} was sent as {:02x?}", cmd, wire);
+    }
+}
+
+#[test]
+fn back_to_back_commands_are_not_mixed_up() {
+    let sent = every_command();
+    let wire: std::vec::Vec<u8> = sent.iter().flat_map(send_command).collect();
+    let received: std::vec::Vec<Command> = parse::<Command>(&wire)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(received, sent);
+}
+
+#[test]
+fn every_response_survives_the_wire() {
+    for resp in [Response::Ack, Response::Nack] {
+        let received = parse::<Response>(&send_command(&resp));
+        assert_eq!(received, vec![Ok(resp)]);
+    }
+}
+
+#[test]
+fn every_request_and_reply_survives_the_wire() {
+    for seq in [0, 1, 254, 255] {
+        for command in every_command() {
+            let request = Request { seq, command };
+            assert_eq!(parse(&send_command(&request)), vec![Ok(request)]);
+        }
+        for response in [Response::Ack, Response::Nack] {
+            let reply = Reply { seq, response };
+            assert_eq!(parse(&send_command(&reply)), vec![Ok(reply)]);
+        }
+    }
+}
+
+/// This is how the Nucleo `pwm_07.rs` declared the command before the `protocol` crate.
+/// It is kept here to show that this suite catches the receiver disagreeing with the sender.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum DriftedCommand {
+    On,
+    Off,
+    Pwm(u16),
+    Interval(u8),
+}
+
+#[test]
+fn a_drifted_receiver_is_caught() {
+    let disagree = every_command().into_iter().any(|cmd| {
+        let expected = match cmd {
+            Command::On => DriftedCommand::On,
+            Command::Off => DriftedCommand::Off,
+            Command::Pwm(level) => DriftedCommand::Pwm(level.into()),
+            Command::Interval(sec) => DriftedCommand::Interval(sec),
+            // did not exist back then
+            Command::Heartbeat
+            | Command::Fade { .. }
+            | Command::Play { .. }
+            | Command::Step { .. }
+            | Command::Morse { .. }
+            | Command::Stop
+            | Command::Blink { .. }
+            | Command::Channel { .. }
+            | Command::Rgb { .. } => return false,
+        };
+        parse::<DriftedCommand>(&send_command(&cmd)) != vec![Ok(expected)]
+    });
+    assert!(disagree);
+}